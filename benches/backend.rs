@@ -0,0 +1,45 @@
+use std::hint::black_box;
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use turtles::tunnel_utils::{Backend, SortedTunnel};
+
+const TUNNEL_LEN: usize = 1000;
+const STREAM_LEN: usize = 100_000;
+
+/// Cycles through every value from `-m` to `m`, like `safe_steps` in `scan.rs`,
+/// so a window of `TUNNEL_LEN` holds at most `2 * m + 1` distinct values and never collapses.
+fn cycling_steps(m: i64) -> Vec<i64> {
+    (-m..=m).cycle().take(STREAM_LEN).collect()
+}
+
+/// Checks and shifts every step of `steps` after the first `TUNNEL_LEN` ones.
+fn scan_with(backend: Backend, steps: &[i64]) -> bool {
+    let mut sorted_tunnel = SortedTunnel::with_backend(steps[..TUNNEL_LEN].to_vec(), backend);
+    let mut safe = true;
+    for &step in &steps[TUNNEL_LEN..] {
+        safe &= sorted_tunnel.is_tunnel_safe(step);
+        sorted_tunnel.shift_right(step);
+    }
+    safe
+}
+
+/// Compares the backends on windows with few and with many distinct values.
+fn backend(c: &mut Criterion) {
+    let mut group = c.benchmark_group("backend");
+    for (cardinality, m) in [("low", 1), ("high", (TUNNEL_LEN as i64 - 1) / 2)] {
+        let steps = cycling_steps(m);
+        group.throughput(Throughput::Elements((STREAM_LEN - TUNNEL_LEN) as u64));
+        for (name, backend) in [
+            ("btree_map", Backend::BTreeMap),
+            ("sorted_vec", Backend::SortedVec),
+        ] {
+            group.bench_with_input(BenchmarkId::new(name, cardinality), &steps, |b, steps| {
+                b.iter(|| scan_with(backend, black_box(steps)))
+            });
+        }
+    }
+    group.finish();
+}
+
+criterion_group!(benches, backend);
+criterion_main!(benches);
@@ -0,0 +1,23 @@
+use std::hint::black_box;
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use turtles::{get_critical_number_parallel, get_critical_number_slice};
+
+/// Both scans go through all steps, as each of 1, -1 and 0 is a sum of the other two.
+fn parallel(c: &mut Criterion) {
+    let steps: Vec<i64> = [1, -1, 0].into_iter().cycle().take(1_000_000).collect();
+    let tunnel_len = 100;
+
+    let mut group = c.benchmark_group("scan_1000000_steps");
+    group.sample_size(10);
+    group.bench_function("sequential", |b| {
+        b.iter(|| get_critical_number_slice(black_box(&steps), tunnel_len))
+    });
+    group.bench_function("parallel", |b| {
+        b.iter(|| get_critical_number_parallel(black_box(&steps), tunnel_len))
+    });
+    group.finish();
+}
+
+criterion_group!(benches, parallel);
+criterion_main!(benches);
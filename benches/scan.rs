@@ -0,0 +1,34 @@
+use std::hint::black_box;
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use turtles::get_critical_number;
+
+/// Cycles through every value from `-m` to `m`, which fit together in a window of `tunnel_len`.
+/// Each of them is a sum of 0 and itself (or -1 and 1 for 0), so the tunnel never collapses
+/// and the whole stream is scanned, with wider windows holding more distinct values.
+fn safe_steps(tunnel_len: usize, stream_len: usize) -> Vec<i64> {
+    let m = (tunnel_len as i64 - 1) / 2;
+    (-m..=m).cycle().take(stream_len).collect()
+}
+
+/// Scans whole streams, see `shift_right.rs` for the cost of moving the window alone.
+fn scan(c: &mut Criterion) {
+    let mut group = c.benchmark_group("get_critical_number");
+    for tunnel_len in [10, 100, 1000] {
+        for stream_len in [10_000, 100_000] {
+            let steps = safe_steps(tunnel_len, stream_len);
+            group.throughput(Throughput::Elements(stream_len as u64));
+            group.bench_with_input(
+                BenchmarkId::new(format!("window_{tunnel_len}"), stream_len),
+                &steps,
+                |b, steps| {
+                    b.iter(|| get_critical_number(black_box(steps).iter().copied(), tunnel_len))
+                },
+            );
+        }
+    }
+    group.finish();
+}
+
+criterion_group!(benches, scan);
+criterion_main!(benches);
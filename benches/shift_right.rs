@@ -0,0 +1,27 @@
+use std::hint::black_box;
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use turtles::tunnel_utils::SortedTunnel;
+
+/// The cost of a single shift should not depend on the tunnel length.
+fn shift_right(c: &mut Criterion) {
+    let mut group = c.benchmark_group("shift_right");
+    for tunnel_len in [10u128, 100, 1000, 10000] {
+        group.bench_with_input(
+            BenchmarkId::from_parameter(tunnel_len),
+            &tunnel_len,
+            |b, &tunnel_len| {
+                let mut sorted_tunnel = SortedTunnel::new((0..tunnel_len).collect());
+                let mut next_step = tunnel_len;
+                b.iter(|| {
+                    sorted_tunnel.shift_right(black_box(next_step));
+                    next_step += 1;
+                });
+            },
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, shift_right);
+criterion_main!(benches);
@@ -1,28 +1,286 @@
 //! # turtles
 //! This library is a set of utilities created for Number Associative Mining and Trading Inc
 //! for finding the point where the tunnel may collapse on their turtles 🐢
+//!
+//! Without the default `std` feature the crate is `no_std` and only needs `alloc`,
+//! but steps cannot be read from [`std::io`] sources then.
+//!
+//! With the `log` feature, events are emitted with the `log` crate as `<event> key=value ...`:
+//! `tunnel created` and `collapse detected` with the `debug` and `info` level, `step shifted` with `trace`,
+//! and `steps ended` with `debug` when the steps run out, with the number of `checked` steps.
 
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+use alloc::{collections::BTreeSet, string::String, vec::Vec};
+use core::{
+    cmp::Ordering,
+    error::Error,
+    fmt::{self, Debug, Display},
+    iter::Peekable,
+    ops::{Add, Sub},
+};
+#[cfg(feature = "std")]
 use std::{
-    fmt::Debug,
-    ops::{Add, Mul},
+    io::{self, BufRead, Read},
+    str::FromStr,
 };
-use tunnel_utils::SortedTunnel;
+use tunnel_utils::{CheckedAdd, CheckedSub, SortedTunnel};
+
+/// Emits an event with the `log` crate at the given level, and compiles to nothing without the `log` feature.
+macro_rules! log_event {
+    ($level:ident, $($arg:tt)+) => {
+        #[cfg(feature = "log")]
+        log::$level!($($arg)+)
+    };
+}
 
 /// Holds information on what value would (`step`) cause the tunnel to collapse
 /// and at which step/on which line (`index`) it would happen
-#[derive(Debug, PartialEq)]
-pub struct IndexedStep<T>
-where
-    T: Ord + Add<Output = T>,
-{
+///
+/// With the `serde` feature enabled, it can be serialized and deserialized with fields named `step` and `index`.
+///
+/// ```
+/// # #[cfg(feature = "serde")]
+/// # {
+/// use turtles::IndexedStep;
+///
+/// let critical_number = IndexedStep {step: 14u128, index: 4};
+/// let json = serde_json::to_string(&critical_number).unwrap();
+///
+/// assert_eq!(json, r#"{"step":14,"index":4}"#);
+/// assert_eq!(serde_json::from_str::<IndexedStep<u128>>(&json).unwrap(), critical_number);
+/// # }
+/// ```
+///
+/// It can be stored in hashed collections, e.g. to deduplicate collapses found in many files,
+/// and it's `Copy` whenever the step is.
+///
+/// ```
+/// use std::collections::HashSet;
+/// use turtles::IndexedStep;
+///
+/// let critical_number = IndexedStep {step: 14u128, index: 4};
+/// let collapses: HashSet<_> = [critical_number, critical_number].into_iter().collect();
+///
+/// assert_eq!(collapses.len(), 1);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct IndexedStep<T> {
     pub step: T,
-    pub index: usize,
+    pub index: StepIndex,
+}
+
+/// Type of [`IndexedStep::index`], `usize` by default.
+///
+/// With the `u64-index` feature it's `u64`, so that streams longer than `u32::MAX` steps
+/// are indexed correctly on 32-bit targets as well. Code which should build with and without the feature
+/// can use this alias instead of naming the type.
+pub type StepIndex = StepIndexType;
+
+#[cfg(not(feature = "u64-index"))]
+type StepIndexType = usize;
+#[cfg(feature = "u64-index")]
+type StepIndexType = u64;
+
+/// Converts a position within a slice or a preceding fragment to a [`StepIndex`], which is never narrower than `usize`.
+fn step_index(position: usize) -> StepIndex {
+    position as StepIndex
+}
+
+/// Renders the step and its zero-based index.
+///
+/// # Examples
+///
+/// ```
+/// use turtles::IndexedStep;
+///
+/// let critical_number = IndexedStep {step: 14, index: 4};
+///
+/// assert_eq!(critical_number.to_string(), "step 14 at index 4");
+/// ```
+impl<T: Display> Display for IndexedStep<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "step {} at index {}", self.step, self.index)
+    }
+}
+
+impl<T> IndexedStep<T> {
+    /// Returns the position of the step starting from 1, so the line it's on when the steps are read one per line.
+    /// [`index`](IndexedStep::index) itself stays zero-based.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use turtles::IndexedStep;
+    ///
+    /// let critical_number = IndexedStep {step: 14, index: 4};
+    ///
+    /// assert_eq!(critical_number.line_number(), 5);
+    /// assert_eq!(critical_number.index, 4);
+    /// ```
+    pub fn line_number(&self) -> StepIndex {
+        self.index + 1
+    }
+}
+
+/// Allows to treat a collapse as an error, see [`analyze`].
+impl<T: Debug + Display> Error for IndexedStep<T> {}
+
+/// Returned when there are not enough steps to check if the tunnel would collapse:
+/// at least one step has to follow the first `tunnel_len` steps.
+#[derive(Debug, PartialEq, Eq)]
+pub struct InsufficientData {
+    pub required: usize,
+    pub available: usize,
+}
+
+impl Display for InsufficientData {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "at least {} steps are required, but only {} are available",
+            self.required, self.available
+        )
+    }
+}
+
+impl Error for InsufficientData {}
+
+/// Every way in which analysing the tunnel can fail.
+///
+/// # Examples
+///
+/// ```
+/// use turtles::get_critical_number_reader;
+/// use turtles::TurtleError;
+///
+/// let input = "5\n4\nabc\n9\n".as_bytes();
+/// let error = get_critical_number_reader(input, 2).unwrap_err();
+///
+/// assert!(matches!(error, TurtleError::Parse {line: 3, ref content} if content == "abc"));
+/// ```
+#[derive(Debug)]
+pub enum TurtleError {
+    /// The steps couldn't be read. Available with the `std` feature.
+    #[cfg(feature = "std")]
+    Io(io::Error),
+    /// The `content` of a `line` (starting from 1) isn't a valid step.
+    Parse { line: usize, content: String },
+    /// The `line` (starting from 1) is blank, but more steps follow it.
+    BlankLine { line: usize },
+    /// There are no steps to check after the preceding fragment.
+    InsufficientData(InsufficientData),
+    /// The step at `index` (starting from 0) repeats an earlier step of the preceding fragment,
+    /// which has to be all-distinct.
+    DuplicateStep { index: usize },
+    /// Binary input of `length` bytes doesn't split into whole 8-byte steps.
+    PartialBinaryStep { length: usize },
+}
+
+impl Display for TurtleError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            #[cfg(feature = "std")]
+            TurtleError::Io(e) => write!(f, "{e}"),
+            TurtleError::Parse { line, content } => {
+                write!(f, "line {line}: invalid number '{content}'")
+            }
+            TurtleError::BlankLine { line } => {
+                write!(f, "line {line}: blank line before more steps")
+            }
+            TurtleError::InsufficientData(e) => write!(f, "{e}"),
+            TurtleError::DuplicateStep { index } => {
+                write!(
+                    f,
+                    "step at index {index} repeats an earlier step of the preceding fragment"
+                )
+            }
+            TurtleError::PartialBinaryStep { length } => {
+                write!(
+                    f,
+                    "binary input of {length} bytes isn't made of whole 8-byte steps"
+                )
+            }
+        }
+    }
+}
+
+impl Error for TurtleError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            #[cfg(feature = "std")]
+            TurtleError::Io(e) => Some(e),
+            TurtleError::Parse { .. } => None,
+            TurtleError::BlankLine { .. } => None,
+            TurtleError::InsufficientData(e) => Some(e),
+            TurtleError::DuplicateStep { .. } => None,
+            TurtleError::PartialBinaryStep { .. } => None,
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<io::Error> for TurtleError {
+    fn from(e: io::Error) -> Self {
+        TurtleError::Io(e)
+    }
+}
+
+impl From<InsufficientData> for TurtleError {
+    fn from(e: InsufficientData) -> Self {
+        TurtleError::InsufficientData(e)
+    }
+}
+
+/// Returned when a preceding fragment can't be restored from bytes,
+/// see [`SortedTunnel::from_bytes`](tunnel_utils::SortedTunnel::from_bytes).
+#[derive(Debug, PartialEq, Eq)]
+pub enum DecodeError {
+    /// The format version byte isn't one this version of the crate can read.
+    UnsupportedVersion(u8),
+    /// The flags byte has bits set which no flag is assigned to.
+    UnknownFlags(u8),
+    /// The bytes end before the encoded fragment does.
+    UnexpectedEnd,
+    /// There are bytes left after the encoded fragment.
+    TrailingBytes,
+    /// An age is repeated or not smaller than the number of steps.
+    InvalidAge(u64),
+    /// The insertion counter of the oldest step doesn't fit in `usize` on this target.
+    CounterOverflow(u64),
+}
+
+impl Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DecodeError::UnsupportedVersion(version) => {
+                write!(f, "unsupported format version {version}")
+            }
+            DecodeError::UnknownFlags(flags) => write!(f, "unknown flags {flags:#04x}"),
+            DecodeError::UnexpectedEnd => write!(f, "unexpected end of the encoded fragment"),
+            DecodeError::TrailingBytes => write!(f, "bytes left after the encoded fragment"),
+            DecodeError::InvalidAge(age) => write!(f, "invalid age {age}"),
+            DecodeError::CounterOverflow(counter) => {
+                write!(f, "insertion counter {counter} doesn't fit in usize")
+            }
+        }
+    }
 }
 
+impl Error for DecodeError {}
+
 /// Consumes `steps` until it finds a step at which tunnel would collapse.
 /// Tunnel collapses if the next step cannot be represented as a sum of 2 from `tunnel_len` preceding elements.
 /// Returns `None` if the tunnel is safe (including the case when `tunnel_len` is bigger or equal to `steps` length).
 ///
+/// Both signed and unsigned steps are supported, and they only have to be `Clone`.
+///
+/// A `tunnel_len` of 0 or 1 leaves no pair of elements to sum up to the next step,
+/// so the first step after the preceding fragment always collapses the tunnel.
+///
 /// # Examples
 ///
 /// ```
@@ -48,142 +306,4222 @@ where
 /// let answer = get_critical_number(steps, tunnel_len);
 ///
 /// assert_eq!(answer, None);
+///
+///
+/// let steps = vec![5u128, 4, 7, 9, 14].into_iter();
+/// let tunnel_len = 3;
+/// let answer = get_critical_number(steps, tunnel_len);
+///
+/// assert_eq!(answer, Some(IndexedStep {step: 14u128, index: 4}));
+///
+///
+/// // steps can be negative
+/// let steps = vec![10i64, -3, 8, 7, -1].into_iter();
+/// let tunnel_len = 3;
+/// let answer = get_critical_number(steps, tunnel_len);
+///
+/// // 7 == -3 + 10, but -1 is not a sum of 2 from -3, 8 and 7
+/// assert_eq!(answer, Some(IndexedStep {step: -1i64, index: 4}));
+///
+///
+/// let steps = vec![20i32, -5, 1, 15, -4].into_iter();
+/// let tunnel_len = 3;
+/// let answer = get_critical_number(steps, tunnel_len);
+///
+/// assert_eq!(answer, None);
+///
+///
+/// // a single preceding element cannot be added to itself, even if the step is twice as big
+/// let steps = vec![5, 10, 20].into_iter();
+/// let tunnel_len = 1;
+/// let answer = get_critical_number(steps, tunnel_len);
+///
+/// assert_eq!(answer, Some(IndexedStep {step: 10, index: 1}));
+///
+///
+/// // steps which are `Clone`, but not `Copy`, like arbitrary-precision integers
+/// #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+/// struct BigStep(Box<u128>);
+///
+/// impl std::ops::Add for BigStep {
+///     type Output = BigStep;
+///
+///     fn add(self, other: Self) -> Self::Output {
+///         BigStep(Box::new(*self.0 + *other.0))
+///     }
+/// }
+///
+/// let steps = [5, 4, 7, 9, 14].map(|step| BigStep(Box::new(step))).into_iter();
+/// let tunnel_len = 3;
+/// let answer = get_critical_number(steps, tunnel_len);
+///
+/// assert_eq!(answer, Some(IndexedStep {step: BigStep(Box::new(14)), index: 4}));
 /// ```
-pub fn get_critical_number<T: Ord + Add<Output = T> + Copy + Debug + Mul<u128, Output = T>>(
-    mut steps_in_tunnel: impl Iterator<Item = T>,
+pub fn get_critical_number<T: Ord + Add<Output = T> + Clone + Debug>(
+    steps_in_tunnel: impl Iterator<Item = T>,
     tunnel_len: usize,
 ) -> Option<IndexedStep<T>> {
-    // first tunnel_len steps are removed from the iterator
-    let tunnel = steps_in_tunnel.by_ref().take(tunnel_len).collect();
+    get_critical_number_with_offset(steps_in_tunnel, tunnel_len, 0)
+}
+
+/// Works like [`get_critical_number`], but also counts the steps which were checked and kept the tunnel safe.
+/// The first `tunnel_len` steps are never checked, and neither the collapsing step nor any step after it is counted.
+///
+/// # Examples
+///
+/// ```
+/// use turtles::get_critical_number_counted;
+/// use turtles::IndexedStep;
+///
+/// let steps = vec![5, 4, 7, 9, 14, 12].into_iter();
+/// let answer = get_critical_number_counted(steps, 3);
+///
+/// // only 9 was safe
+/// assert_eq!(answer, (Some(IndexedStep {step: 14, index: 4}), 1));
+///
+///
+/// let steps = vec![5, 4, 7, 9, 11].into_iter();
+/// let answer = get_critical_number_counted(steps, 3);
+///
+/// assert_eq!(answer, (None, 2));
+///
+///
+/// let steps = vec![5, 4].into_iter();
+/// let answer = get_critical_number_counted(steps, 3);
+///
+/// assert_eq!(answer, (None, 0));
+/// ```
+pub fn get_critical_number_counted<T: Ord + Add<Output = T> + Clone + Debug>(
+    steps_in_tunnel: impl Iterator<Item = T>,
+    tunnel_len: usize,
+) -> (Option<IndexedStep<T>>, StepIndex) {
+    let mut consumed_steps: StepIndex = 0;
+    let critical_number =
+        get_critical_number(steps_in_tunnel.inspect(|_| consumed_steps += 1), tunnel_len);
+
+    let safe_steps = match &critical_number {
+        Some(critical_number) => critical_number.index - step_index(tunnel_len),
+        None => consumed_steps.saturating_sub(step_index(tunnel_len)),
+    };
+    (critical_number, safe_steps)
+}
+
+/// Works like [`get_critical_number`], but `steps` are the remainder of a longer stream
+/// starting at `base_index`, and the index is reported within that stream.
+///
+/// # Examples
+///
+/// ```
+/// use turtles::get_critical_number_with_offset;
+/// use turtles::IndexedStep;
+///
+/// let steps = vec![1, 2, 5, 4, 7, 9, 14];
+/// // the first 2 steps were already checked elsewhere
+/// let remainder = steps[2..].iter().copied();
+/// let answer = get_critical_number_with_offset(remainder, 3, 2);
+///
+/// assert_eq!(answer, Some(IndexedStep {step: 14, index: 6}));
+///
+///
+/// # #[cfg(feature = "u64-index")]
+/// # {
+/// use turtles::StepIndex;
+///
+/// // with the `u64-index` feature the stream can have more than u32::MAX steps on any target
+/// let base_index = StepIndex::from(u32::MAX) + 2;
+/// let answer = get_critical_number_with_offset(steps[2..].iter().copied(), 3, base_index);
+///
+/// assert_eq!(answer, Some(IndexedStep {step: 14, index: 4_294_967_301}));
+/// # }
+/// ```
+pub fn get_critical_number_with_offset<T: Ord + Add<Output = T> + Clone + Debug>(
+    steps_in_tunnel: impl Iterator<Item = T>,
+    tunnel_len: usize,
+    base_index: StepIndex,
+) -> Option<IndexedStep<T>> {
+    get_critical_number_k(steps_in_tunnel, tunnel_len, 2).map(|critical_number| IndexedStep {
+        step: critical_number.step,
+        index: base_index + critical_number.index,
+    })
+}
+
+/// Works like [`get_critical_number`], but returns an error if there are no steps after the first `tunnel_len`
+/// steps, so the tunnel couldn't be checked at all. `Ok(None)` always means the tunnel is safe.
+///
+/// # Examples
+///
+/// ```
+/// use turtles::try_get_critical_number;
+/// use turtles::{IndexedStep, InsufficientData, TurtleError};
+///
+/// let steps = vec![5, 4, 7, 9, 14].into_iter();
+/// let tunnel_len = 3;
+/// let answer = try_get_critical_number(steps, tunnel_len).unwrap();
+///
+/// assert_eq!(answer, Some(IndexedStep {step: 14, index: 4}));
+///
+///
+/// let steps = vec![5, 4, 18].into_iter();
+/// let tunnel_len = 3;
+/// let error = try_get_critical_number(steps, tunnel_len).unwrap_err();
+///
+/// assert!(matches!(error, TurtleError::InsufficientData(InsufficientData {required: 4, available: 3})));
+/// ```
+pub fn try_get_critical_number<T: Ord + Add<Output = T> + Clone + Debug>(
+    steps_in_tunnel: impl Iterator<Item = T>,
+    tunnel_len: usize,
+) -> Result<Option<IndexedStep<T>>, TurtleError> {
     let mut steps_in_tunnel = steps_in_tunnel.peekable();
+    let tunnel: Vec<T> = steps_in_tunnel.by_ref().take(tunnel_len).collect();
 
-    // if the iterator is empty, the tunnel is safe
-    if steps_in_tunnel.peek() == None {
-        return None;
+    if steps_in_tunnel.peek().is_none() {
+        return Err(InsufficientData {
+            required: tunnel_len + 1,
+            available: tunnel.len(),
+        }
+        .into());
     }
 
+    Ok(get_critical_number(
+        tunnel.into_iter().chain(steps_in_tunnel),
+        tunnel_len,
+    ))
+}
+
+/// Checks if the tunnel never collapses, which is when [`get_critical_number`] returns `None`,
+/// stopping at the first step which collapses it.
+///
+/// # Examples
+///
+/// ```
+/// use turtles::is_stream_safe;
+///
+/// assert!(!is_stream_safe(vec![5, 4, 7, 9, 14].into_iter(), 3));
+/// assert!(is_stream_safe(vec![5, 4, 9, 13].into_iter(), 2));
+///
+/// // nothing follows the preceding fragment
+/// assert!(is_stream_safe(vec![5, 4, 18].into_iter(), 3));
+/// ```
+pub fn is_stream_safe<T: Ord + Add<Output = T> + Clone + Debug>(
+    mut steps_in_tunnel: impl Iterator<Item = T>,
+    tunnel_len: usize,
+) -> bool {
+    let tunnel = steps_in_tunnel.by_ref().take(tunnel_len).collect();
     let mut sorted_tunnel = SortedTunnel::new(tunnel);
 
-    for (index, step) in steps_in_tunnel.enumerate() {
-        if !sorted_tunnel.is_tunnel_safe(step) {
-            // we need to add tunnel_len, as the for loop starts from this offset
-            return Some(IndexedStep {
-                index: index + tunnel_len,
-                step,
-            });
-        }
+    steps_in_tunnel.all(|step| {
+        let is_safe = sorted_tunnel.is_tunnel_safe(step.clone());
         sorted_tunnel.shift_right(step);
-    }
-
-    None
+        is_safe
+    })
 }
 
-/// Low level utilities for examining tunnels for turtles
-mod tunnel_utils {
-    use std::cmp::Ordering;
-    use std::collections::{BTreeMap, VecDeque};
-    use std::fmt::Debug;
-    use std::ops::{Add, Mul};
+/// Checks if `next` is a sum of 2 elements of `preamble`, so if the tunnel stays safe after it.
+/// It's a shortcut for a one-off [`SortedTunnel::is_tunnel_safe`] on a fragment made of `preamble`.
+///
+/// # Examples
+///
+/// ```
+/// use turtles::validates;
+///
+/// assert!(validates(&[5, 4, 7], 9));
+/// assert!(!validates(&[5, 4, 7], 14));
+///
+/// // an element appearing once isn't added to itself
+/// assert!(!validates(&[5, 4], 10));
+/// assert!(validates(&[5, 5], 10));
+///
+/// // an empty preamble never validates anything
+/// assert!(!validates(&[], 0));
+/// ```
+pub fn validates<T: Ord + Add<Output = T> + Clone + Debug>(preamble: &[T], next: T) -> bool {
+    SortedTunnel::new(preamble.to_vec()).is_tunnel_safe(next)
+}
 
-    /// This trait allows us to handle inserting duplicate keys to the BTreeMap.
-    trait AddDuplicate<T>
-    where
-        T: Ord + Add<Output = T> + Copy,
-    {
-        fn add_duplicate(&mut self, step: T, age: usize);
+/// Works like [`get_critical_number`], but a collapse is returned as an error,
+/// so it can be propagated with `?` where a collapse means the whole pipeline failed.
+///
+/// # Examples
+///
+/// ```
+/// use turtles::analyze;
+/// use turtles::IndexedStep;
+///
+/// fn check_all(files: Vec<Vec<u128>>) -> Result<(), IndexedStep<u128>> {
+///     for steps in files {
+///         analyze(steps.into_iter(), 2)?;
+///     }
+///     Ok(())
+/// }
+///
+/// assert_eq!(check_all(vec![vec![5, 4, 9], vec![1, 2, 3]]), Ok(()));
+/// assert_eq!(check_all(vec![vec![5, 4, 9], vec![5, 4, 10]]), Err(IndexedStep {step: 10, index: 2}));
+/// ```
+pub fn analyze<T: Ord + Add<Output = T> + Clone + Debug>(
+    steps_in_tunnel: impl Iterator<Item = T>,
+    tunnel_len: usize,
+) -> Result<(), IndexedStep<T>> {
+    match get_critical_number(steps_in_tunnel, tunnel_len) {
+        Some(critical_number) => Err(critical_number),
+        None => Ok(()),
     }
+}
 
-    /// Holds information about preceding fragment of the tunnel.
-    ///
-    /// `BTreeMap` is used because it's sorted and because of its fast lookup times.
-    /// It cannot store duplicate keys, so the underlying queue represents how many keys are present.
-    /// `usize` values represent their order in the preceding tunnel fragment.
-    ///
-    /// This is efficient because checking sums of elements which are smaller than our target is the majority
-    /// of operations conducted in the process. Also, we will only remove elements from the start of the queue
-    /// and append at its end, so `VecDeque` is also appropriate.
-    pub struct SortedTunnel<T>
-    where
-        T: Ord + Add<Output = T> + Copy,
-    {
-        tunnel_map: BTreeMap<T, VecDeque<usize>>,
-        tunnel_length: usize,
-    }
+/// Works like [`get_critical_number`], but returns an error if a step appears more than once
+/// in the first `tunnel_len` steps. Later steps can repeat each other like before.
+///
+/// # Examples
+///
+/// ```
+/// use turtles::get_critical_number_strict;
+/// use turtles::{IndexedStep, TurtleError};
+///
+/// let steps = vec![5, 4, 7, 9, 14].into_iter();
+/// let tunnel_len = 3;
+/// let answer = get_critical_number_strict(steps, tunnel_len).unwrap();
+///
+/// assert_eq!(answer, Some(IndexedStep {step: 14, index: 4}));
+///
+///
+/// let steps = vec![5, 4, 5, 9, 14].into_iter();
+/// let tunnel_len = 3;
+/// let error = get_critical_number_strict(steps, tunnel_len).unwrap_err();
+///
+/// assert!(matches!(error, TurtleError::DuplicateStep {index: 2}));
+/// ```
+pub fn get_critical_number_strict<T: Ord + Add<Output = T> + Clone + Debug>(
+    steps_in_tunnel: impl Iterator<Item = T>,
+    tunnel_len: usize,
+) -> Result<Option<IndexedStep<T>>, TurtleError> {
+    let mut steps_in_tunnel = steps_in_tunnel.peekable();
+    let tunnel: Vec<T> = steps_in_tunnel.by_ref().take(tunnel_len).collect();
 
-    impl<T> AddDuplicate<T> for SortedTunnel<T>
-    where
-        T: Ord + Add<Output = T> + Copy,
-    {
-        fn add_duplicate(&mut self, step: T, age: usize) {
-            self.tunnel_map
-                .entry(step)
-                .and_modify(|ages| ages.push_back(age))
-                .or_insert(VecDeque::from([age]));
-        }
+    let mut distinct_steps = BTreeSet::new();
+    if let Some(index) = tunnel.iter().position(|step| !distinct_steps.insert(step)) {
+        return Err(TurtleError::DuplicateStep { index });
     }
 
-    impl<T: Ord + Add<Output = T> + Copy + Debug + Mul<u128, Output = T>> SortedTunnel<T> {
-        pub fn new(tunnel: Vec<T>) -> SortedTunnel<T> {
-            let mut sorted_tunnel = SortedTunnel {
-                tunnel_map: BTreeMap::new(),
-                tunnel_length: tunnel.len() - 1,
-            };
-            for (age, step) in tunnel.iter().enumerate() {
-                sorted_tunnel.add_duplicate(*step, age);
-            }
-            sorted_tunnel
-        }
-
-        /// Removes the oldest step in preceding fragment.
-        fn remove_oldest_step(&mut self) {
-            let mut target_step: Option<T> = None;
-            for (step, ages) in self.tunnel_map.iter() {
-                if ages.contains(&0) {
-                    target_step = Some(*step);
-                    break;
-                }
-            }
-            if let Some(step) = target_step {
-                let ages_ref = self.tunnel_map.get_mut(&step).unwrap();
-                ages_ref.pop_front();
-                if ages_ref.len() == 0 {
-                    self.tunnel_map.remove(&step);
-                }
+    Ok(get_critical_number(
+        tunnel.into_iter().chain(steps_in_tunnel),
+        tunnel_len,
+    ))
+}
 
-                self.tunnel_map
-                    .values_mut()
-                    .for_each(|ages| ages.iter_mut().for_each(|age| *age -= 1));
+/// Works like [`get_critical_number`], but doesn't need an iterator over `steps`.
+///
+/// # Examples
+///
+/// ```
+/// use turtles::get_critical_number_slice;
+/// use turtles::IndexedStep;
+///
+/// let steps = [5, 4, 7, 9, 14];
+/// let tunnel_len = 3;
+/// let answer = get_critical_number_slice(&steps, tunnel_len);
+///
+/// assert_eq!(answer, Some(IndexedStep {step: 14, index: 4}));
+/// ```
+pub fn get_critical_number_slice<T: Ord + Add<Output = T> + Clone + Debug>(
+    steps: &[T],
+    tunnel_len: usize,
+) -> Option<IndexedStep<T>> {
+    get_critical_number(steps.iter().cloned(), tunnel_len)
+}
+
+/// Finds the first collapse for every offset `steps` could start from, as if the steps before it didn't exist.
+/// The preceding fragment of an offset is made of the `tunnel_len` steps starting at it,
+/// so only offsets followed by at least one more step are reported.
+/// The index of a collapse is counted from the start of `steps`, not from the offset.
+///
+/// This is expensive: every offset scans the rest of `steps` again,
+/// so it takes O(n * n * w) comparisons for n steps and a preceding fragment of w elements.
+/// It's meant for exploring small inputs, and it reuses the same [`SortedTunnel`] for every offset.
+///
+/// # Examples
+///
+/// ```
+/// use turtles::critical_numbers_per_offset;
+/// use turtles::IndexedStep;
+///
+/// let steps = [5, 4, 7, 9, 14, 30, 44];
+/// let tunnel_len = 3;
+/// let answer = critical_numbers_per_offset(&steps, tunnel_len);
+///
+/// assert_eq!(
+///     answer,
+///     vec![
+///         Some(IndexedStep {step: 14, index: 4}),
+///         Some(IndexedStep {step: 14, index: 4}),
+///         Some(IndexedStep {step: 30, index: 5}),
+///         None,
+///     ]
+/// );
+/// ```
+pub fn critical_numbers_per_offset<T: Ord + Add<Output = T> + Clone + Debug>(
+    steps: &[T],
+    tunnel_len: usize,
+) -> Vec<Option<IndexedStep<T>>> {
+    let mut sorted_tunnel = SortedTunnel::new(Vec::new());
+
+    (0..steps.len().saturating_sub(tunnel_len))
+        .map(|offset| {
+            let checked_from = offset + tunnel_len;
+            sorted_tunnel.reset(steps[offset..checked_from].to_vec());
+
+            for (index, step) in steps.iter().enumerate().skip(checked_from) {
+                if !sorted_tunnel.is_tunnel_safe(step.clone()) {
+                    return Some(IndexedStep {
+                        step: step.clone(),
+                        index: step_index(index),
+                    });
+                }
+                sorted_tunnel.shift_right(step.clone());
+            }
+
+            None
+        })
+        .collect()
+}
+
+/// Works like [`get_critical_number_slice`], but scans parts of `steps` in parallel.
+///
+/// `steps` are split into chunks, and every chunk also includes `tunnel_len` steps preceding it,
+/// so that its first step can be checked without the previous chunk.
+/// These steps are scanned twice, so it only pays off when `steps` are many times longer than `tunnel_len`.
+/// The earliest collapse from all chunks is returned, so the answer is the same as from the sequential scan.
+///
+/// Available with the `rayon` feature.
+///
+/// # Examples
+///
+/// ```
+/// use turtles::get_critical_number_parallel;
+/// use turtles::get_critical_number_slice;
+///
+/// // each of 1, -1 and 0 is a sum of the other two, so only the last step collapses the tunnel
+/// let mut steps: Vec<i64> = [1, -1, 0].into_iter().cycle().take(100_000).collect();
+/// steps.push(3);
+///
+/// assert_eq!(get_critical_number_parallel(&steps, 100), get_critical_number_slice(&steps, 100));
+///
+/// // the earlier collapse wins, wherever the chunks start
+/// steps[50_001] = 3;
+/// for tunnel_len in [3, 100, 10_000] {
+///     assert_eq!(get_critical_number_parallel(&steps, tunnel_len), get_critical_number_slice(&steps, tunnel_len));
+/// }
+/// ```
+#[cfg(feature = "rayon")]
+pub fn get_critical_number_parallel<T: Ord + Add<Output = T> + Clone + Debug + Send + Sync>(
+    steps: &[T],
+    tunnel_len: usize,
+) -> Option<IndexedStep<T>> {
+    use rayon::prelude::*;
+
+    let checked_steps = steps.len().checked_sub(tunnel_len)?;
+    // a few chunks for each thread, so that one slow chunk doesn't keep the others waiting
+    let chunk_len = (checked_steps / (rayon::current_num_threads() * 4)).max(1);
+
+    (0..checked_steps)
+        .into_par_iter()
+        .step_by(chunk_len)
+        .find_map_first(|first_checked| {
+            let chunk_end = (tunnel_len + first_checked + chunk_len).min(steps.len());
+            let chunk = &steps[first_checked..chunk_end];
+            get_critical_number_slice(chunk, tunnel_len).map(|critical_number| IndexedStep {
+                step: critical_number.step,
+                index: critical_number.index + step_index(first_checked),
+            })
+        })
+}
+
+/// Decides if the tunnel stays safe after the next step, given its preceding fragment.
+///
+/// [`SumOfTwo`] is the rule used by [`get_critical_number`], and [`SumOfK`] the one used by [`get_critical_number_k`].
+/// Any `Fn(&SortedTunnel<T>, T) -> bool` is a rule too.
+///
+/// # Examples
+///
+/// ```
+/// use turtles::tunnel_utils::SortedTunnel;
+/// use turtles::{get_critical_number_with_rule, CollapseRule, IndexedStep};
+///
+/// /// The next step has to be a product of 2 different preceding elements.
+/// struct ProductOfTwo;
+///
+/// impl CollapseRule<u128> for ProductOfTwo {
+///     fn is_safe(&self, window: &SortedTunnel<u128>, candidate: u128) -> bool {
+///         let window = window.window();
+///         (0..window.len())
+///             .any(|i| (i + 1..window.len()).any(|j| window[i] * window[j] == candidate))
+///     }
+/// }
+///
+/// let steps = vec![2, 3, 5, 6, 15, 11].into_iter();
+/// let answer = get_critical_number_with_rule(steps, 3, ProductOfTwo);
+///
+/// assert_eq!(answer, Some(IndexedStep {step: 11, index: 5}));
+/// ```
+pub trait CollapseRule<T: Ord + Add<Output = T> + Clone> {
+    /// Checks if `candidate` keeps the tunnel safe right after the steps in `window`.
+    fn is_safe(&self, window: &SortedTunnel<T>, candidate: T) -> bool;
+}
+
+impl<T, F> CollapseRule<T> for F
+where
+    T: Ord + Add<Output = T> + Clone,
+    F: Fn(&SortedTunnel<T>, T) -> bool,
+{
+    fn is_safe(&self, window: &SortedTunnel<T>, candidate: T) -> bool {
+        self(window, candidate)
+    }
+}
+
+/// The next step has to be a sum of 2 preceding elements, see [`SortedTunnel::is_tunnel_safe`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SumOfTwo;
+
+impl<T: Ord + Add<Output = T> + Clone + Debug> CollapseRule<T> for SumOfTwo {
+    fn is_safe(&self, window: &SortedTunnel<T>, candidate: T) -> bool {
+        window.is_tunnel_safe(candidate)
+    }
+}
+
+/// The next step has to be a sum of exactly `k` preceding elements, see [`SortedTunnel::is_tunnel_safe_k`].
+/// Like [`SumOfTwo`], it respects the self-pair and repeat settings of the preceding fragment.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SumOfK(pub usize);
+
+impl<T: Ord + Add<Output = T> + Clone + Debug> CollapseRule<T> for SumOfK {
+    fn is_safe(&self, window: &SortedTunnel<T>, candidate: T) -> bool {
+        window.is_tunnel_safe_k(candidate, self.0)
+    }
+}
+
+/// The next step has to be a sum of 2 preceding elements which differ by at most the given spread,
+/// see [`SortedTunnel::safe_pair_within`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SumOfTwoWithin<T>(pub T);
+
+impl<T: Ord + Add<Output = T> + CheckedSub + Clone + Debug> CollapseRule<T> for SumOfTwoWithin<T> {
+    fn is_safe(&self, window: &SortedTunnel<T>, candidate: T) -> bool {
+        window.safe_pair_within(candidate, self.0.clone()).is_some()
+    }
+}
+
+/// The next step has to be a sum or a difference of 2 preceding elements,
+/// see [`SortedTunnel::is_tunnel_safe`] and [`SortedTunnel::safe_difference`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SumOrDifference;
+
+impl<T: Ord + Add<Output = T> + CheckedSub + Clone + Debug> CollapseRule<T> for SumOrDifference {
+    fn is_safe(&self, window: &SortedTunnel<T>, candidate: T) -> bool {
+        window.is_tunnel_safe(candidate.clone()) || window.safe_difference(candidate).is_some()
+    }
+}
+
+/// Works like [`get_critical_number`], but `rule` decides if the tunnel collapses.
+///
+/// # Examples
+///
+/// ```
+/// use turtles::tunnel_utils::SortedTunnel;
+/// use turtles::{get_critical_number_with_rule, IndexedStep, SumOfK, SumOfTwo};
+///
+/// let steps = vec![5, 4, 7, 16, 12];
+///
+/// assert_eq!(
+///     get_critical_number_with_rule(steps.clone().into_iter(), 3, SumOfTwo),
+///     Some(IndexedStep {step: 16, index: 3})
+/// );
+/// assert_eq!(
+///     get_critical_number_with_rule(steps.clone().into_iter(), 3, SumOfK(3)),
+///     Some(IndexedStep {step: 12, index: 4})
+/// );
+///
+/// // the tunnel is safe as long as the steps grow
+/// let growing = |window: &SortedTunnel<i32>, candidate| window.newest() < Some(candidate);
+/// assert_eq!(
+///     get_critical_number_with_rule(steps.into_iter(), 3, growing),
+///     Some(IndexedStep {step: 12, index: 4})
+/// );
+/// ```
+pub fn get_critical_number_with_rule<T, R>(
+    steps_in_tunnel: impl Iterator<Item = T>,
+    tunnel_len: usize,
+    rule: R,
+) -> Option<IndexedStep<T>>
+where
+    T: Ord + Add<Output = T> + Clone + Debug,
+    R: CollapseRule<T>,
+{
+    find_critical_number(steps_in_tunnel, tunnel_len, |sorted_tunnel, step| {
+        rule.is_safe(sorted_tunnel, step)
+    })
+}
+
+/// Works like [`get_critical_number`], but the 2 preceding elements summing up to the next step
+/// can differ by at most `max_spread`.
+/// A spread which would overflow `T` exceeds any `max_spread`, see [`SortedTunnel::safe_pair_within`].
+///
+/// # Examples
+///
+/// ```
+/// use turtles::{get_critical_number, get_critical_number_bounded};
+/// use turtles::IndexedStep;
+///
+/// let steps = vec![1, 5, 6, 10, 11, 15];
+/// let tunnel_len = 4;
+///
+/// // 11 == 1 + 10 == 5 + 6, but 15 == 5 + 10 only
+/// assert_eq!(get_critical_number(steps.clone().into_iter(), tunnel_len), None);
+/// assert_eq!(
+///     get_critical_number_bounded(steps.clone().into_iter(), tunnel_len, 1),
+///     Some(IndexedStep {step: 15, index: 5})
+/// );
+/// assert_eq!(get_critical_number_bounded(steps.into_iter(), tunnel_len, 5), None);
+///
+///
+/// // -100 + 100 is a sum, but the spread of 200 doesn't fit in `i8`
+/// let steps = vec![-100i8, 100, 0].into_iter();
+/// let answer = get_critical_number_bounded(steps, 2, i8::MAX);
+///
+/// assert_eq!(answer, Some(IndexedStep {step: 0, index: 2}));
+/// ```
+pub fn get_critical_number_bounded<T: Ord + Add<Output = T> + CheckedSub + Clone + Debug>(
+    steps_in_tunnel: impl Iterator<Item = T>,
+    tunnel_len: usize,
+    max_spread: T,
+) -> Option<IndexedStep<T>> {
+    get_critical_number_with_rule(steps_in_tunnel, tunnel_len, SumOfTwoWithin(max_spread))
+}
+
+/// Works like [`get_critical_number`], but the next step can also be the difference
+/// of 2 preceding elements (the larger minus the smaller one) to keep the tunnel safe.
+///
+/// # Examples
+///
+/// ```
+/// use turtles::{get_critical_number, get_critical_number_sum_or_diff};
+/// use turtles::IndexedStep;
+///
+/// let steps = vec![10, 3, 7, 4, 20];
+/// let tunnel_len = 2;
+///
+/// // 7 == 10 - 3 and 4 == 7 - 3, but neither is a sum
+/// assert_eq!(
+///     get_critical_number(steps.clone().into_iter(), tunnel_len),
+///     Some(IndexedStep {step: 7, index: 2})
+/// );
+/// assert_eq!(
+///     get_critical_number_sum_or_diff(steps.into_iter(), tunnel_len),
+///     Some(IndexedStep {step: 20, index: 4})
+/// );
+/// ```
+pub fn get_critical_number_sum_or_diff<T: Ord + Add<Output = T> + CheckedSub + Clone + Debug>(
+    steps_in_tunnel: impl Iterator<Item = T>,
+    tunnel_len: usize,
+) -> Option<IndexedStep<T>> {
+    get_critical_number_with_rule(steps_in_tunnel, tunnel_len, SumOrDifference)
+}
+
+/// Works like [`get_critical_number`], but the tunnel collapses if the next step cannot be represented
+/// as a sum of exactly `k` from `tunnel_len` preceding elements.
+/// Each preceding element can be used only as many times as it appears in the preceding fragment.
+///
+/// `k == 2` is the regular rule. `k == 1` is a trivial case where the next step has to be equal to one of
+/// the preceding elements. Checking a single step takes O(`tunnel_len`) for `k == 2`, with a two-cursor scan,
+/// but every `k > 2` is considerably more expensive, as it takes O(`tunnel_len`^`k`) in the worst case.
+///
+/// # Panics
+///
+/// Panics if `k == 0`, as no step can be represented as an empty sum.
+///
+/// # Examples
+///
+/// ```
+/// use turtles::get_critical_number_k;
+/// use turtles::IndexedStep;
+///
+/// let steps = vec![5, 4, 7, 16, 12].into_iter();
+/// let tunnel_len = 3;
+/// let answer = get_critical_number_k(steps, tunnel_len, 3);
+///
+/// // 16 == 5 + 4 + 7, but 12 can't be represented with 4, 7 and 16
+/// assert_eq!(answer, Some(IndexedStep {step: 12, index: 4}));
+///
+///
+/// let steps = vec![5, 5, 4, 14].into_iter();
+/// let tunnel_len = 3;
+/// let answer = get_critical_number_k(steps, tunnel_len, 3);
+///
+/// assert_eq!(answer, None);
+///
+///
+/// // 5 appears only once, so it can't be used twice
+/// let steps = vec![5, 4, 3, 13].into_iter();
+/// let tunnel_len = 3;
+/// let answer = get_critical_number_k(steps, tunnel_len, 3);
+///
+/// assert_eq!(answer, Some(IndexedStep {step: 13, index: 3}));
+///
+///
+/// let steps = vec![-6i64, 10, -2, 4, 2].into_iter();
+/// let tunnel_len = 4;
+/// let answer = get_critical_number_k(steps, tunnel_len, 3);
+///
+/// // 2 == -6 + 10 + -2
+/// assert_eq!(answer, None);
+/// ```
+pub fn get_critical_number_k<T: Ord + Add<Output = T> + Clone + Debug>(
+    steps_in_tunnel: impl Iterator<Item = T>,
+    tunnel_len: usize,
+    k: usize,
+) -> Option<IndexedStep<T>> {
+    assert!(k > 0, "A step cannot be represented as a sum of 0 elements");
+
+    get_critical_number_with_rule(steps_in_tunnel, tunnel_len, SumOfK(k))
+}
+
+/// Works like [`get_critical_number`], but sums which would overflow `T` are skipped instead of
+/// panicking (in debug builds) or wrapping around (in release builds).
+///
+/// Skipping is correct, because an overflowing sum can never be equal to a step representable by `T`.
+///
+/// # Examples
+///
+/// ```
+/// use turtles::get_critical_number_checked;
+/// use turtles::IndexedStep;
+///
+/// let steps = vec![1, 5, u128::MAX - 15, u128::MAX, u128::MAX - 10].into_iter();
+/// let tunnel_len = 4;
+/// let answer = get_critical_number_checked(steps, tunnel_len);
+///
+/// // 1 + u128::MAX overflows, but 5 + (u128::MAX - 15) keeps the tunnel safe
+/// assert_eq!(answer, None);
+///
+///
+/// let steps = vec![u128::MAX - 1, u128::MAX - 2, 5, 10].into_iter();
+/// let tunnel_len = 3;
+/// let answer = get_critical_number_checked(steps, tunnel_len);
+///
+/// assert_eq!(answer, Some(IndexedStep {step: 10, index: 3}));
+/// ```
+pub fn get_critical_number_checked<T: Ord + Add<Output = T> + Clone + Debug + CheckedAdd>(
+    steps_in_tunnel: impl Iterator<Item = T>,
+    tunnel_len: usize,
+) -> Option<IndexedStep<T>> {
+    find_critical_number(steps_in_tunnel, tunnel_len, |sorted_tunnel, step| {
+        sorted_tunnel.is_tunnel_safe_checked(step)
+    })
+}
+
+/// Works like [`get_critical_number`], but calls `on_safe` for every step after the first `tunnel_len` steps
+/// which kept the tunnel safe, e.g. to report progress over a long stream.
+/// `on_safe` isn't called for the step at which the tunnel collapses.
+///
+/// # Examples
+///
+/// ```
+/// use turtles::get_critical_number_with_callback;
+/// use turtles::IndexedStep;
+///
+/// let steps = vec![5, 4, 7, 9, 11, 14].into_iter();
+/// let tunnel_len = 3;
+/// let mut safe_steps = vec![];
+/// let answer = get_critical_number_with_callback(steps, tunnel_len, |safe| safe_steps.push(safe.step));
+///
+/// assert_eq!(answer, Some(IndexedStep {step: 14, index: 5}));
+/// assert_eq!(safe_steps, vec![9, 11]);
+/// ```
+pub fn get_critical_number_with_callback<T, F>(
+    steps_in_tunnel: impl Iterator<Item = T>,
+    tunnel_len: usize,
+    mut on_safe: F,
+) -> Option<IndexedStep<T>>
+where
+    T: Ord + Add<Output = T> + Clone + Debug,
+    F: FnMut(&IndexedStep<T>),
+{
+    let verdicts = Verdicts::new(steps_in_tunnel, tunnel_len, |sorted_tunnel, step| {
+        sorted_tunnel.is_tunnel_safe(step)
+    });
+
+    for (step, is_safe) in verdicts {
+        if !is_safe {
+            return Some(step);
+        }
+        on_safe(&step);
+    }
+
+    None
+}
+
+/// Works like [`get_critical_number`], but folds every checked step into an accumulator,
+/// starting from `init`, and returns it together with the first collapse.
+///
+/// `f` gets the accumulator, the step and whether it kept the tunnel safe, for every step
+/// after the first `tunnel_len` steps in their order. The step at which the tunnel collapses is folded
+/// with `false` as well, before the scan stops, so no step after it is folded.
+///
+/// # Examples
+///
+/// ```
+/// use turtles::scan_fold;
+/// use turtles::IndexedStep;
+///
+/// let steps = vec![5, 4, 7, 9, 11, 14, 1].into_iter();
+/// let tunnel_len = 3;
+/// let (sum, answer) = scan_fold(steps, tunnel_len, 0, |sum, step, is_safe| {
+///     if is_safe { sum + step } else { sum }
+/// });
+///
+/// assert_eq!(answer, Some(IndexedStep {step: 14, index: 5}));
+/// assert_eq!(sum, 9 + 11);
+///
+///
+/// // the collapsing step is the last one folded
+/// let steps = vec![5, 4, 7, 9, 11, 14, 1].into_iter();
+/// let (verdicts, _) = scan_fold(steps, tunnel_len, vec![], |mut verdicts, step, is_safe| {
+///     verdicts.push((*step, is_safe));
+///     verdicts
+/// });
+///
+/// assert_eq!(verdicts, vec![(9, true), (11, true), (14, false)]);
+/// ```
+pub fn scan_fold<T, A, F>(
+    steps_in_tunnel: impl Iterator<Item = T>,
+    tunnel_len: usize,
+    init: A,
+    mut f: F,
+) -> (A, Option<IndexedStep<T>>)
+where
+    T: Ord + Add<Output = T> + Clone + Debug,
+    F: FnMut(A, &T, bool) -> A,
+{
+    let verdicts = Verdicts::new(steps_in_tunnel, tunnel_len, |sorted_tunnel, step| {
+        sorted_tunnel.is_tunnel_safe(step)
+    });
+
+    let mut accumulator = init;
+    for (step, is_safe) in verdicts {
+        accumulator = f(accumulator, &step.step, is_safe);
+        if !is_safe {
+            return (accumulator, Some(step));
+        }
+    }
+
+    (accumulator, None)
+}
+
+/// Works like [`get_critical_number_checked`] for `u128` steps, but the sums are computed with
+/// `u128::saturating_add`, so no values can make it panic.
+///
+/// A sum which overflowed is never equal to a step, even to `u128::MAX`,
+/// while 2 elements summing up to exactly `u128::MAX` still keep the tunnel safe.
+///
+/// # Examples
+///
+/// ```
+/// use turtles::get_critical_number_saturating;
+/// use turtles::IndexedStep;
+///
+/// // get_critical_number would overflow adding the first two steps
+/// let steps = vec![u128::MAX - 1, u128::MAX - 2, u128::MAX].into_iter();
+/// let tunnel_len = 2;
+/// let answer = get_critical_number_saturating(steps, tunnel_len);
+///
+/// assert_eq!(answer, Some(IndexedStep {step: u128::MAX, index: 2}));
+///
+///
+/// // (u128::MAX - 10) + 10 is exactly u128::MAX
+/// let steps = vec![u128::MAX - 10, 10, u128::MAX - 5, u128::MAX].into_iter();
+/// let tunnel_len = 3;
+/// let answer = get_critical_number_saturating(steps, tunnel_len);
+///
+/// assert_eq!(answer, None);
+/// ```
+pub fn get_critical_number_saturating(
+    steps_in_tunnel: impl Iterator<Item = u128>,
+    tunnel_len: usize,
+) -> Option<IndexedStep<u128>> {
+    find_critical_number(steps_in_tunnel, tunnel_len, |sorted_tunnel, step| {
+        sorted_tunnel
+            .safe_pair_by(|candidate_a, candidate_b| {
+                match candidate_a.saturating_add(*candidate_b) {
+                    // the sum might have saturated, so it's checked without adding
+                    u128::MAX if step == u128::MAX => candidate_b.cmp(&(u128::MAX - candidate_a)),
+                    sum => sum.cmp(&step),
+                }
+            })
+            .is_some()
+    })
+}
+
+/// Works like [`get_critical_number`], but pulls the steps from an async `stream`,
+/// so a collapse is found as soon as the step causing it arrives.
+///
+/// Available with the `stream` feature.
+///
+/// # Examples
+///
+/// ```
+/// # #[cfg(feature = "stream")]
+/// # {
+/// use turtles::get_critical_number_stream;
+/// use turtles::IndexedStep;
+///
+/// let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+///
+/// let steps = tokio_stream::iter(vec![5, 4, 7, 9, 14]);
+/// let answer = runtime.block_on(get_critical_number_stream(steps, 3));
+///
+/// assert_eq!(answer, Some(IndexedStep {step: 14, index: 4}));
+///
+///
+/// let steps = tokio_stream::iter(vec![5, 4]);
+/// let answer = runtime.block_on(get_critical_number_stream(steps, 3));
+///
+/// assert_eq!(answer, None);
+/// # }
+/// ```
+#[cfg(feature = "stream")]
+pub async fn get_critical_number_stream<S, T>(
+    mut steps_in_tunnel: S,
+    tunnel_len: usize,
+) -> Option<IndexedStep<T>>
+where
+    S: futures_util::Stream<Item = T> + Unpin,
+    T: Ord + Add<Output = T> + Clone + Debug,
+{
+    use futures_util::StreamExt;
+
+    let mut tunnel = Vec::with_capacity(tunnel_len);
+    while tunnel.len() < tunnel_len {
+        tunnel.push(steps_in_tunnel.next().await?);
+    }
+    let mut sorted_tunnel = SortedTunnel::new(tunnel);
+
+    let mut index = step_index(tunnel_len);
+    while let Some(step) = steps_in_tunnel.next().await {
+        if !sorted_tunnel.is_tunnel_safe(step.clone()) {
+            return Some(IndexedStep { step, index });
+        }
+        sorted_tunnel.shift_right(step);
+        index += 1;
+    }
+
+    None
+}
+
+/// Works like [`get_critical_number`], but no step ever leaves the preceding fragment:
+/// after the first `preamble_len` steps, every checked step is appended to it.
+///
+/// Unlike the sliding fragment, which holds `tunnel_len` steps,
+/// the growing one ends up holding every step before the collapse, so memory grows with the input.
+///
+/// # Examples
+///
+/// ```
+/// use turtles::{get_critical_number, get_critical_number_growing};
+/// use turtles::IndexedStep;
+///
+/// let steps = vec![5, 4, 7, 9, 14, 12];
+/// let preamble_len = 3;
+///
+/// // 14 == 5 + 9, but 5 is no longer in the sliding fragment
+/// assert_eq!(
+///     get_critical_number(steps.clone().into_iter(), preamble_len),
+///     Some(IndexedStep {step: 14, index: 4})
+/// );
+/// assert_eq!(get_critical_number_growing(steps.into_iter(), preamble_len), None);
+/// ```
+pub fn get_critical_number_growing<T: Ord + Add<Output = T> + Clone + Debug>(
+    steps_in_tunnel: impl Iterator<Item = T>,
+    preamble_len: usize,
+) -> Option<IndexedStep<T>> {
+    // a window which is never full only appends the shifted steps
+    let verdicts = Verdicts::with_tunnel(
+        steps_in_tunnel,
+        preamble_len,
+        |sorted_tunnel, step| sorted_tunnel.is_tunnel_safe(step),
+        |preamble| SortedTunnel::with_capacity(usize::MAX, preamble),
+    );
+    collapses(verdicts).next()
+}
+
+/// Works like [`get_critical_number`], but for floating-point steps.
+/// A sum of 2 preceding elements represents the next step if they differ by at most `epsilon`.
+///
+/// A `NaN` step always collapses the tunnel, and a `NaN` in the preceding fragment never sums up to any step.
+///
+/// # Examples
+///
+/// ```
+/// use turtles::get_critical_number_f64;
+/// use turtles::IndexedStep;
+///
+/// let steps = vec![0.1, 0.2, 0.3].into_iter();
+/// let tunnel_len = 2;
+/// let answer = get_critical_number_f64(steps, tunnel_len, 1e-9);
+///
+/// // 0.1 + 0.2 == 0.30000000000000004
+/// assert_eq!(answer, None);
+///
+///
+/// let steps = vec![0.1, 0.2, 0.3].into_iter();
+/// let tunnel_len = 2;
+/// let answer = get_critical_number_f64(steps, tunnel_len, 0.0);
+///
+/// assert_eq!(answer, Some(IndexedStep {step: 0.3, index: 2}));
+///
+///
+/// let steps = vec![1.0, 2.0, f64::NAN].into_iter();
+/// let tunnel_len = 2;
+/// let answer = get_critical_number_f64(steps, tunnel_len, 1e-9).unwrap();
+///
+/// assert!(answer.step.is_nan());
+/// assert_eq!(answer.index, 2);
+/// ```
+pub fn get_critical_number_f64(
+    steps_in_tunnel: impl Iterator<Item = f64>,
+    tunnel_len: usize,
+    epsilon: f64,
+) -> Option<IndexedStep<f64>> {
+    let steps_in_tunnel = steps_in_tunnel.map(TotalOrderF64);
+
+    find_critical_number(steps_in_tunnel, tunnel_len, |sorted_tunnel, step| {
+        !step.0.is_nan()
+            && sorted_tunnel
+                .safe_pair_by(|candidate_a, candidate_b| {
+                    let sum = candidate_a.0 + candidate_b.0;
+                    if (sum - step.0).abs() <= epsilon {
+                        Ordering::Equal
+                    } else {
+                        sum.total_cmp(&step.0)
+                    }
+                })
+                .is_some()
+    })
+    .map(|critical_number| IndexedStep {
+        step: critical_number.step.0,
+        index: critical_number.index,
+    })
+}
+
+/// Tolerance of [`critical_number_wasm`], large enough for the rounding errors of adding 2 typical steps.
+#[cfg(feature = "wasm")]
+const WASM_EPSILON: f64 = 1e-9;
+
+/// Works like [`get_critical_number_f64`] with an `epsilon` of `1e-9`, but returns only the index,
+/// as the caller already holds the steps.
+///
+/// Available with the `wasm` feature, which exports it to JavaScript with `wasm-bindgen`.
+/// The library isn't a `cdylib` by default, as that would break `no_std` builds,
+/// so `wasm-pack` cannot build it directly. Instead, the module can be built with
+/// `cargo rustc --lib --release --target wasm32-unknown-unknown --features wasm --crate-type cdylib`,
+/// then its bindings are generated with
+/// `wasm-bindgen --target web --out-dir pkg target/wasm32-unknown-unknown/release/turtles.wasm`,
+/// and it can be used like this:
+///
+/// ```js
+/// import init, { critical_number_wasm } from "./pkg/turtles.js";
+///
+/// await init();
+/// // steps are passed as a Float64Array, and undefined means that the tunnel will not crumble
+/// const index = critical_number_wasm(new Float64Array([5, 4, 7, 9, 14]), 3);
+/// ```
+///
+/// # Examples
+///
+/// ```
+/// use turtles::critical_number_wasm;
+///
+/// assert_eq!(critical_number_wasm(&[5.0, 4.0, 7.0, 9.0, 14.0], 3), Some(4));
+/// assert_eq!(critical_number_wasm(&[0.1, 0.2, 0.3], 2), None);
+/// ```
+#[cfg(feature = "wasm")]
+#[wasm_bindgen::prelude::wasm_bindgen]
+#[allow(clippy::unnecessary_cast)]
+pub fn critical_number_wasm(steps: &[f64], tunnel_len: usize) -> Option<usize> {
+    // the steps are in memory, so the index of any of them fits in `usize`
+    get_critical_number_f64(steps.iter().copied(), tunnel_len, WASM_EPSILON)
+        .map(|critical_number| critical_number.index as usize)
+}
+
+/// Works like [`get_critical_number`], but reads the steps from `reader`,
+/// separated by ASCII whitespace or commas, e.g. one number per line or `5,4,7` on a single line.
+/// The lines are read one by one, so the whole input is never held in memory
+/// and reading stops as soon as the tunnel collapses.
+///
+/// Blank lines are allowed only at the end of the input, otherwise the first one is a [`TurtleError::BlankLine`].
+/// Returns [`TurtleError::Parse`] naming the line if some step on it isn't a valid number,
+/// and [`TurtleError::Io`] if it cannot be read.
+///
+/// Available with the `std` feature.
+///
+/// # Examples
+///
+/// ```
+/// use turtles::get_critical_number_reader;
+/// use turtles::IndexedStep;
+///
+/// let input = "5\n4\n7\n9\n14\n\n".as_bytes();
+/// let tunnel_len = 3;
+/// let answer = get_critical_number_reader(input, tunnel_len).unwrap();
+///
+/// assert_eq!(answer, Some(IndexedStep {step: 14, index: 4}));
+///
+///
+/// let input = "5\n4\nabc\n9\n".as_bytes();
+/// let tunnel_len = 2;
+/// let error = get_critical_number_reader(input, tunnel_len).unwrap_err();
+///
+/// assert_eq!(error.to_string(), "line 3: invalid number 'abc'");
+///
+///
+/// let input = "5, 4,7\n9 14\n".as_bytes();
+/// let tunnel_len = 3;
+/// let answer = get_critical_number_reader(input, tunnel_len).unwrap();
+///
+/// assert_eq!(answer, Some(IndexedStep {step: 14, index: 4}));
+///
+///
+/// let input = "5,4,x7".as_bytes();
+/// let error = get_critical_number_reader(input, tunnel_len).unwrap_err();
+///
+/// assert_eq!(error.to_string(), "line 1: invalid number 'x7'");
+///
+///
+/// let input = "5\n4\n\n7\n".as_bytes();
+/// let error = get_critical_number_reader(input, tunnel_len).unwrap_err();
+///
+/// assert_eq!(error.to_string(), "line 3: blank line before more steps");
+/// ```
+#[cfg(feature = "std")]
+pub fn get_critical_number_reader<R: BufRead>(
+    reader: R,
+    tunnel_len: usize,
+) -> Result<Option<IndexedStep<u128>>, TurtleError> {
+    get_critical_number_reader_as(reader, tunnel_len)
+}
+
+/// Works like [`get_critical_number_reader`], but the steps are parsed as `T` instead of `u128`.
+/// A step which doesn't fit in `T` is a [`TurtleError::Parse`] error.
+///
+/// Available with the `std` feature.
+///
+/// # Examples
+///
+/// ```
+/// use turtles::get_critical_number_reader_as;
+/// use turtles::IndexedStep;
+///
+/// let input = "5\n4\n7\n9\n14\n".as_bytes();
+/// let answer = get_critical_number_reader_as::<u64, _>(input, 3).unwrap();
+///
+/// assert_eq!(answer, Some(IndexedStep {step: 14u64, index: 4}));
+///
+///
+/// let input = "5\n-4\n".as_bytes();
+/// let error = get_critical_number_reader_as::<u64, _>(input, 3).unwrap_err();
+///
+/// assert_eq!(error.to_string(), "line 2: invalid number '-4'");
+/// ```
+#[cfg(feature = "std")]
+pub fn get_critical_number_reader_as<T, R>(
+    reader: R,
+    tunnel_len: usize,
+) -> Result<Option<IndexedStep<T>>, TurtleError>
+where
+    T: Ord + Add<Output = T> + Clone + Debug + FromStr,
+    R: BufRead,
+{
+    let mut error = None;
+    let steps_in_tunnel = parse_lines(reader).map_while(|step| match step {
+        Ok(step) => Some(step),
+        Err(e) => {
+            error = Some(e);
+            None
+        }
+    });
+
+    let critical_number = get_critical_number(steps_in_tunnel, tunnel_len);
+    match error {
+        Some(e) => Err(e),
+        None => Ok(critical_number),
+    }
+}
+
+/// Consumes all `steps` and finds every step at which the tunnel would collapse.
+/// The preceding fragment keeps moving past each collapse,
+/// so every step is checked against the `tunnel_len` elements directly preceding it.
+/// Returns an empty `Vec` if the tunnel is safe.
+///
+/// # Examples
+///
+/// ```
+/// use turtles::get_all_critical_numbers;
+/// use turtles::IndexedStep;
+///
+/// let steps = vec![5, 4, 7, 9, 14, 30, 44].into_iter();
+/// let tunnel_len = 3;
+/// let answer = get_all_critical_numbers(steps, tunnel_len);
+///
+/// // 44 == 14 + 30, even though both of them collapsed the tunnel
+/// assert_eq!(
+///     answer,
+///     vec![IndexedStep {step: 14, index: 4}, IndexedStep {step: 30, index: 5}]
+/// );
+///
+///
+/// let steps = vec![5, 4, 9].into_iter();
+/// let tunnel_len = 2;
+/// let answer = get_all_critical_numbers(steps, tunnel_len);
+///
+/// assert_eq!(answer, vec![]);
+/// ```
+pub fn get_all_critical_numbers<T: Ord + Add<Output = T> + Clone + Debug>(
+    steps_in_tunnel: impl Iterator<Item = T>,
+    tunnel_len: usize,
+) -> Vec<IndexedStep<T>> {
+    critical_numbers(steps_in_tunnel, tunnel_len).collect()
+}
+
+/// Finds the last step of `steps` at which the tunnel collapses, like the last element
+/// of [`get_all_critical_numbers`], but without collecting the other collapses.
+///
+/// # Examples
+///
+/// ```
+/// use turtles::get_last_critical_number;
+/// use turtles::IndexedStep;
+///
+/// let steps = [5, 4, 7, 9, 14, 30, 44, 100, 144];
+/// let tunnel_len = 3;
+/// let answer = get_last_critical_number(&steps, tunnel_len);
+///
+/// // 14 and 30 collapse the tunnel too
+/// assert_eq!(answer, Some(IndexedStep {step: 100, index: 7}));
+///
+///
+/// let steps = [5, 4, 9, 13];
+/// let tunnel_len = 2;
+/// let answer = get_last_critical_number(&steps, tunnel_len);
+///
+/// assert_eq!(answer, None);
+/// ```
+pub fn get_last_critical_number<T: Ord + Add<Output = T> + Clone + Debug>(
+    steps: &[T],
+    tunnel_len: usize,
+) -> Option<IndexedStep<T>> {
+    critical_numbers(steps.iter().cloned(), tunnel_len).last()
+}
+
+/// Describes a collapse found by [`get_critical_number_detailed`].
+#[derive(Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CollapseReport<T> {
+    pub critical_number: IndexedStep<T>,
+    /// The greatest sum of 2 preceding elements smaller than the critical step.
+    pub nearest_below: Option<T>,
+    /// The smallest sum of 2 preceding elements greater than the critical step.
+    pub nearest_above: Option<T>,
+}
+
+/// Works like [`get_critical_number`], but also reports the sums of 2 preceding elements
+/// which came closest to the critical step, below and above it.
+///
+/// No sum is equal to the critical step, so every sum is either below or above it.
+/// Many pairs can give the same nearest sum, but only the sum is reported.
+/// The nearest sum on a side is `None` if no sum lands there,
+/// so both are `None` if the preceding fragment has fewer than 2 elements.
+///
+/// # Examples
+///
+/// ```
+/// use turtles::get_critical_number_detailed;
+/// use turtles::{CollapseReport, IndexedStep};
+///
+/// let steps = vec![5, 4, 7, 9, 14].into_iter();
+/// let tunnel_len = 3;
+/// let answer = get_critical_number_detailed(steps, tunnel_len);
+///
+/// // 4 + 9 and 7 + 9 came closest to 14
+/// assert_eq!(
+///     answer,
+///     Some(CollapseReport {
+///         critical_number: IndexedStep {step: 14, index: 4},
+///         nearest_below: Some(13),
+///         nearest_above: Some(16),
+///     })
+/// );
+///
+///
+/// let steps = vec![5, 4, 100].into_iter();
+/// let tunnel_len = 2;
+/// let answer = get_critical_number_detailed(steps, tunnel_len).unwrap();
+///
+/// assert_eq!((answer.nearest_below, answer.nearest_above), (Some(9), None));
+/// ```
+pub fn get_critical_number_detailed<T: Ord + Add<Output = T> + Clone + Debug>(
+    steps_in_tunnel: impl Iterator<Item = T>,
+    tunnel_len: usize,
+) -> Option<CollapseReport<T>> {
+    let mut nearest_sums = None;
+    let critical_number =
+        find_critical_number(steps_in_tunnel, tunnel_len, |sorted_tunnel, step| {
+            let is_safe = sorted_tunnel.is_tunnel_safe(step.clone());
+            if !is_safe {
+                nearest_sums = Some(sorted_tunnel.nearest_sums(step));
+            }
+            is_safe
+        })?;
+
+    // the check which found the collapse has set them
+    let (nearest_below, nearest_above) = nearest_sums?;
+    Some(CollapseReport {
+        critical_number,
+        nearest_below,
+        nearest_above,
+    })
+}
+
+/// Works like [`get_critical_number`], but also returns the preceding fragment which couldn't represent
+/// the critical step, in ascending order with duplicates (see [`SortedTunnel::window`]).
+///
+/// # Examples
+///
+/// ```
+/// use turtles::get_critical_number_with_window;
+/// use turtles::IndexedStep;
+///
+/// let steps = vec![5, 4, 7, 9, 14].into_iter();
+/// let tunnel_len = 3;
+/// let answer = get_critical_number_with_window(steps, tunnel_len);
+///
+/// assert_eq!(answer, Some((IndexedStep {step: 14, index: 4}, vec![4, 7, 9])));
+/// ```
+pub fn get_critical_number_with_window<T: Ord + Add<Output = T> + Clone + Debug>(
+    steps_in_tunnel: impl Iterator<Item = T>,
+    tunnel_len: usize,
+) -> Option<(IndexedStep<T>, Vec<T>)> {
+    let mut window = None;
+    let critical_number =
+        find_critical_number(steps_in_tunnel, tunnel_len, |sorted_tunnel, step| {
+            let is_safe = sorted_tunnel.is_tunnel_safe(step);
+            if !is_safe {
+                window = Some(sorted_tunnel.window());
+            }
+            is_safe
+        })?;
+
+    // the check which found the collapse has set it
+    Some((critical_number, window?))
+}
+
+/// Lazily finds every step at which the tunnel would collapse, like [`get_all_critical_numbers`].
+/// `steps` are consumed only as far as needed to find the next collapse.
+///
+/// # Examples
+///
+/// ```
+/// use turtles::critical_numbers;
+/// use turtles::IndexedStep;
+///
+/// let steps = vec![5, 4, 7, 9, 14, 30, 44, 100];
+/// let tunnel_len = 3;
+/// let mut answer = critical_numbers(steps, tunnel_len);
+///
+/// assert_eq!(answer.next(), Some(IndexedStep {step: 14, index: 4}));
+/// assert_eq!(answer.next(), Some(IndexedStep {step: 30, index: 5}));
+///
+///
+/// // the stream is endless, but only the first two collapses are needed
+/// let steps = 1..;
+/// let tunnel_len = 2;
+/// let answer: Vec<_> = critical_numbers(steps, tunnel_len).take(2).collect();
+///
+/// assert_eq!(answer, vec![IndexedStep {step: 4, index: 3}, IndexedStep {step: 5, index: 4}]);
+/// ```
+pub fn critical_numbers<I, T>(
+    steps_in_tunnel: I,
+    tunnel_len: usize,
+) -> impl Iterator<Item = IndexedStep<T>>
+where
+    I: IntoIterator<Item = T>,
+    T: Ord + Add<Output = T> + Clone + Debug,
+{
+    collapses(Verdicts::new(
+        steps_in_tunnel.into_iter(),
+        tunnel_len,
+        |sorted_tunnel: &SortedTunnel<T>, step| sorted_tunnel.is_tunnel_safe(step),
+    ))
+}
+
+/// Lazily checks every step after the first `tunnel_len` steps, yielding it with whether
+/// the tunnel stayed safe. The preceding fragment moves forward after every step, even after a collapse.
+///
+/// The first `tunnel_len` steps are not yielded, as there is no preceding fragment to check them against.
+///
+/// # Examples
+///
+/// ```
+/// use turtles::verdicts;
+///
+/// let steps = vec![5, 4, 7, 9, 14, 21];
+/// let tunnel_len = 3;
+/// let answer: Vec<_> = verdicts(steps, tunnel_len).collect();
+///
+/// assert_eq!(answer, vec![(9, true), (14, false), (21, true)]);
+///
+///
+/// // no step comes after the first tunnel_len steps
+/// let steps = vec![1, 2, 3];
+/// let tunnel_len = 3;
+///
+/// assert_eq!(verdicts(steps, tunnel_len).next(), None);
+/// ```
+pub fn verdicts<I, T>(steps_in_tunnel: I, tunnel_len: usize) -> impl Iterator<Item = (T, bool)>
+where
+    I: IntoIterator<Item = T>,
+    T: Ord + Add<Output = T> + Clone + Debug,
+{
+    Verdicts::new(
+        steps_in_tunnel.into_iter(),
+        tunnel_len,
+        |sorted_tunnel: &SortedTunnel<T>, step| sorted_tunnel.is_tunnel_safe(step),
+    )
+    .map(|(indexed_step, is_safe)| (indexed_step.step, is_safe))
+}
+
+/// Lazily yields the smallest and the greatest sum of 2 preceding elements for every step
+/// after the first `tunnel_len` steps, from the preceding fragment the step is checked against.
+/// See [`SortedTunnel::sum_bounds`].
+///
+/// Nothing is yielded for the first `tunnel_len` steps, and nothing at all if `tunnel_len` is smaller than 2,
+/// as there's no pair of elements to sum up then.
+///
+/// # Panics
+///
+/// Panics in the same cases as [`SortedTunnel::sum_bounds`], when adding up the elements overflows `T`.
+///
+/// # Examples
+///
+/// ```
+/// use turtles::sum_bounds;
+///
+/// let steps = vec![5, 4, 7, 9, 14];
+/// let tunnel_len = 3;
+/// let answer: Vec<_> = sum_bounds(steps, tunnel_len).collect();
+///
+/// // [4, 5, 7] and then [4, 7, 9]
+/// assert_eq!(answer, vec![(9, 12), (11, 16)]);
+///
+///
+/// let steps = vec![5, 4, 7];
+/// let tunnel_len = 1;
+///
+/// assert_eq!(sum_bounds(steps, tunnel_len).next(), None);
+/// ```
+pub fn sum_bounds<I, T>(steps_in_tunnel: I, tunnel_len: usize) -> impl Iterator<Item = (T, T)>
+where
+    I: IntoIterator<Item = T>,
+    T: Ord + Add<Output = T> + Clone + Debug,
+{
+    let mut steps_in_tunnel = steps_in_tunnel.into_iter();
+    let mut sorted_tunnel = None;
+
+    core::iter::from_fn(move || {
+        // first tunnel_len steps are removed from the iterator when the first bounds are needed
+        let sorted_tunnel = sorted_tunnel.get_or_insert_with(|| {
+            SortedTunnel::new(steps_in_tunnel.by_ref().take(tunnel_len).collect())
+        });
+        let step = steps_in_tunnel.next()?;
+        let bounds = sorted_tunnel.sum_bounds()?;
+        sorted_tunnel.shift_right(step);
+        Some(bounds)
+    })
+}
+
+/// Configurable tunnel analysis, which can be built with [`TunnelAnalyzer::new`].
+///
+/// The default options reproduce [`get_critical_number`] and [`get_all_critical_numbers`]:
+/// the next step has to be a sum of 2 different preceding elements.
+/// Sums which would overflow are not handled, see [`get_critical_number_checked`] for that.
+///
+/// # Examples
+///
+/// ```
+/// use turtles::TunnelAnalyzer;
+/// use turtles::IndexedStep;
+///
+/// let analyzer = TunnelAnalyzer::new(3).build();
+/// let steps = vec![5, 4, 7, 9, 14];
+///
+/// assert_eq!(analyzer.first_critical(steps), Some(IndexedStep {step: 14, index: 4}));
+///
+///
+/// let analyzer = TunnelAnalyzer::new(3).addends(3).allow_self_pair(true).build();
+/// let steps = vec![5, 4, 7, 15, 16, 20];
+///
+/// // 15 == 4 + 4 + 7, but neither 16 nor 20 is a sum of 3 preceding elements
+/// assert_eq!(analyzer.all_critical(steps), vec![IndexedStep {step: 16, index: 4}, IndexedStep {step: 20, index: 5}]);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TunnelAnalyzer {
+    tunnel_len: usize,
+    addends: usize,
+    allow_self_pair: bool,
+}
+
+/// Builder of [`TunnelAnalyzer`], see each method for the options.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TunnelAnalyzerBuilder {
+    tunnel_len: usize,
+    addends: usize,
+    allow_self_pair: bool,
+}
+
+impl TunnelAnalyzer {
+    /// Starts building an analyzer checking each step against `tunnel_len` preceding elements.
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new(tunnel_len: usize) -> TunnelAnalyzerBuilder {
+        TunnelAnalyzerBuilder {
+            tunnel_len,
+            addends: 2,
+            allow_self_pair: false,
+        }
+    }
+
+    /// Consumes `steps` until it finds a step at which tunnel would collapse.
+    pub fn first_critical<T: Ord + Add<Output = T> + Clone + Debug>(
+        &self,
+        steps_in_tunnel: impl IntoIterator<Item = T>,
+    ) -> Option<IndexedStep<T>> {
+        self.critical_numbers(steps_in_tunnel).next()
+    }
+
+    /// Consumes all `steps` and finds every step at which the tunnel would collapse.
+    pub fn all_critical<T: Ord + Add<Output = T> + Clone + Debug>(
+        &self,
+        steps_in_tunnel: impl IntoIterator<Item = T>,
+    ) -> Vec<IndexedStep<T>> {
+        self.critical_numbers(steps_in_tunnel).collect()
+    }
+
+    fn critical_numbers<T: Ord + Add<Output = T> + Clone + Debug>(
+        &self,
+        steps_in_tunnel: impl IntoIterator<Item = T>,
+    ) -> impl Iterator<Item = IndexedStep<T>> {
+        let TunnelAnalyzer {
+            tunnel_len,
+            addends,
+            allow_self_pair,
+        } = *self;
+
+        collapses(Verdicts::new(
+            steps_in_tunnel.into_iter(),
+            tunnel_len,
+            move |sorted_tunnel: &SortedTunnel<T>, step| {
+                sorted_tunnel.is_sum_of(step, addends, allow_self_pair)
+            },
+        ))
+    }
+}
+
+impl TunnelAnalyzerBuilder {
+    /// Sets how many preceding elements have to sum up to the next step, 2 by default.
+    /// See [`get_critical_number_k`] for the cost of other values.
+    pub fn addends(mut self, k: usize) -> Self {
+        self.addends = k;
+        self
+    }
+
+    /// Sets if a preceding element can be added to itself even if it appears only once, `false` by default.
+    ///
+    /// With more than 2 addends, this allows to use every element any number of times.
+    /// With a single addend it makes no difference.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use turtles::TunnelAnalyzer;
+    /// use turtles::IndexedStep;
+    ///
+    /// let steps = vec![5, 3, 10];
+    ///
+    /// assert_eq!(TunnelAnalyzer::new(2).build().first_critical(steps.clone()), Some(IndexedStep {step: 10, index: 2}));
+    /// assert_eq!(TunnelAnalyzer::new(2).allow_self_pair(true).build().first_critical(steps), None);
+    /// ```
+    pub fn allow_self_pair(mut self, allow_self_pair: bool) -> Self {
+        self.allow_self_pair = allow_self_pair;
+        self
+    }
+
+    /// Creates the analyzer.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the number of addends is 0, as no step can be represented as an empty sum.
+    pub fn build(self) -> TunnelAnalyzer {
+        assert!(
+            self.addends > 0,
+            "A step cannot be represented as a sum of 0 elements"
+        );
+
+        TunnelAnalyzer {
+            tunnel_len: self.tunnel_len,
+            addends: self.addends,
+            allow_self_pair: self.allow_self_pair,
+        }
+    }
+}
+
+/// Finds the contiguous run of at least 2 `steps` that sums up to `target`
+/// (usually the critical number returned by [`get_critical_number`]).
+/// Returns the inclusive start and end indices of the earliest such run, or `None` if there is no such run.
+///
+/// The search uses a sliding window, so it expects non-negative steps and runs in O(n).
+/// The sum of the window never exceeds `target`, as a step is only added after checking
+/// it fits in what's left of `target`, so even steps close to the maximum of `T` cannot overflow it.
+///
+/// # Examples
+///
+/// ```
+/// use turtles::find_collapse_weakness;
+///
+/// let steps = [5, 4, 7, 9, 14];
+/// let answer = find_collapse_weakness(&steps, 20);
+///
+/// assert_eq!(answer, Some((1, 3)));
+///
+///
+/// // a single step equal to the target is not a run
+/// let steps = [5, 4, 14];
+/// let answer = find_collapse_weakness(&steps, 14);
+///
+/// assert_eq!(answer, None);
+///
+///
+/// // the earliest of multiple runs is returned
+/// let steps = [1, 2, 3, 1, 2, 3];
+/// let answer = find_collapse_weakness(&steps, 6);
+///
+/// assert_eq!(answer, Some((0, 2)));
+///
+///
+/// // adding any two of the first steps would overflow
+/// let steps = [u128::MAX - 5, u128::MAX - 3, 2, 3];
+/// assert_eq!(find_collapse_weakness(&steps, 5), Some((2, 3)));
+///
+/// let steps = [u128::MAX - 5, 2, 3, u128::MAX];
+/// assert_eq!(find_collapse_weakness(&steps, u128::MAX), Some((0, 2)));
+/// ```
+pub fn find_collapse_weakness<T: Ord + Add<Output = T> + Sub<Output = T> + Clone>(
+    steps: &[T],
+    target: T,
+) -> Option<(usize, usize)> {
+    // sum of `steps[start..end]`, or `None` if the window is empty
+    let mut sum: Option<T> = None;
+    let mut start = 0;
+
+    for (end, step) in steps.iter().enumerate() {
+        // the oldest steps are removed until `step` fits, comparing without adding it
+        while let Some(window_sum) = sum.take() {
+            if *step <= target.clone() - window_sum.clone() {
+                sum = Some(window_sum);
+                break;
+            }
+            start += 1;
+            if start < end {
+                sum = Some(window_sum - steps[start - 1].clone());
+            }
+        }
+
+        sum = match sum {
+            Some(window_sum) => Some(window_sum + step.clone()),
+            None if *step <= target => {
+                start = end;
+                Some(step.clone())
+            }
+            // even a single step is too much, so the window starts after it
+            None => {
+                start = end + 1;
+                None
+            }
+        };
+
+        if sum.as_ref() == Some(&target) && start < end {
+            return Some((start, end));
+        }
+    }
+
+    None
+}
+
+/// Finds the critical number of `steps` and the contiguous run summing up to it,
+/// then returns the sum of the smallest and the largest step in that run.
+///
+/// Returns `None` if the tunnel is safe, and also if the critical number is found
+/// but no contiguous run of at least 2 steps sums up to it (see [`find_collapse_weakness`]).
+///
+/// # Examples
+///
+/// ```
+/// use turtles::get_tunnel_weakness;
+///
+/// let steps = [5, 4, 7, 9, 20];
+/// let tunnel_len = 3;
+/// let answer = get_tunnel_weakness(&steps, tunnel_len);
+///
+/// // 4 + 7 + 9 == 20
+/// assert_eq!(answer, Some(13));
+///
+///
+/// let steps = [5, 4, 9];
+/// let tunnel_len = 2;
+/// let answer = get_tunnel_weakness(&steps, tunnel_len);
+///
+/// assert_eq!(answer, None);
+///
+///
+/// // 40 collapses the tunnel, but no run sums up to it
+/// let steps = [5, 4, 7, 40];
+/// let tunnel_len = 3;
+/// let answer = get_tunnel_weakness(&steps, tunnel_len);
+///
+/// assert_eq!(answer, None);
+/// ```
+pub fn get_tunnel_weakness<T: Ord + Add<Output = T> + Sub<Output = T> + Clone + Debug>(
+    steps: &[T],
+    tunnel_len: usize,
+) -> Option<T> {
+    let critical = get_critical_number_slice(steps, tunnel_len)?;
+    let (start, end) = find_collapse_weakness(steps, critical.step)?;
+
+    let range = &steps[start..=end];
+    let min = range.iter().min()?;
+    let max = range.iter().max()?;
+    Some(min.clone() + max.clone())
+}
+
+/// Reads all steps from `reader`, in the same format as [`get_critical_number_reader`].
+///
+/// Available with the `std` feature.
+///
+/// # Examples
+///
+/// ```
+/// use turtles::read_steps;
+///
+/// let input = "5,4\n7\n".as_bytes();
+///
+/// assert_eq!(read_steps(input).unwrap(), vec![5, 4, 7]);
+/// assert!(read_steps("5\nx\n".as_bytes()).is_err());
+/// ```
+#[cfg(feature = "std")]
+pub fn read_steps<R: BufRead>(reader: R) -> Result<Vec<u128>, TurtleError> {
+    read_steps_as(reader)
+}
+
+/// Works like [`read_steps`], but the steps are parsed as `T` instead of `u128`.
+///
+/// Available with the `std` feature.
+#[cfg(feature = "std")]
+pub fn read_steps_as<T: FromStr, R: BufRead>(reader: R) -> Result<Vec<T>, TurtleError> {
+    parse_lines(reader).collect()
+}
+
+/// Reads all bytes from `reader` as a flat array of big-endian `u64` steps, 8 bytes each,
+/// instead of the text format of [`read_steps`].
+///
+/// Available with the `std` feature.
+///
+/// # Errors
+///
+/// Returns [`TurtleError::PartialBinaryStep`] if the number of bytes isn't a multiple of 8.
+///
+/// # Examples
+///
+/// ```
+/// use turtles::{read_steps_be_u64, TurtleError};
+///
+/// let input: &[u8] = &[0, 0, 0, 0, 0, 0, 0, 5, 0, 0, 0, 0, 0, 0, 1, 0];
+///
+/// assert_eq!(read_steps_be_u64(input).unwrap(), vec![5, 256]);
+///
+/// let error = read_steps_be_u64(&input[..12]).unwrap_err();
+/// assert!(matches!(error, TurtleError::PartialBinaryStep { length: 12 }));
+/// ```
+#[cfg(feature = "std")]
+pub fn read_steps_be_u64<R: Read>(mut reader: R) -> Result<Vec<u64>, TurtleError> {
+    let mut bytes = Vec::new();
+    reader.read_to_end(&mut bytes)?;
+
+    let (steps, rest) = bytes.as_chunks::<8>();
+    if !rest.is_empty() {
+        return Err(TurtleError::PartialBinaryStep {
+            length: bytes.len(),
+        });
+    }
+    Ok(steps.iter().map(|step| u64::from_be_bytes(*step)).collect())
+}
+
+/// Steps parsed from a whole text, in the same format as [`get_critical_number_reader`].
+///
+/// Available with the `std` feature.
+///
+/// # Examples
+///
+/// ```
+/// use turtles::{StepFile, TurtleError};
+///
+/// let file: StepFile = "5,4\n7\n".parse().unwrap();
+///
+/// assert_eq!(file.steps, vec![5, 4, 7]);
+///
+///
+/// // the error points at the first line which isn't a number
+/// let error = StepFile::try_from("5\nx\ny\n").unwrap_err();
+///
+/// assert!(matches!(error, TurtleError::Parse { line: 2, ref content } if content == "x"));
+/// ```
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StepFile {
+    pub steps: Vec<u128>,
+}
+
+#[cfg(feature = "std")]
+impl FromStr for StepFile {
+    type Err = TurtleError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let steps = read_steps(s.as_bytes())?;
+        Ok(StepFile { steps })
+    }
+}
+
+#[cfg(feature = "std")]
+impl TryFrom<&str> for StepFile {
+    type Error = TurtleError;
+
+    fn try_from(s: &str) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+/// Parses every line of `reader` as steps separated by ASCII whitespace or commas,
+/// tolerating blank lines only at the end.
+#[cfg(feature = "std")]
+fn parse_lines<T: FromStr, R: BufRead>(reader: R) -> impl Iterator<Item = Result<T, TurtleError>> {
+    let mut first_blank_line = None;
+
+    reader.lines().enumerate().flat_map(move |(i, line)| {
+        let line = match line {
+            Ok(line) => line,
+            Err(e) => return vec![Err(e.into())],
+        };
+        let mut tokens = line
+            .split(|c: char| c.is_ascii_whitespace() || c == ',')
+            .filter(|token| !token.is_empty())
+            .peekable();
+
+        // a blank line is an error only if some step follows it
+        if tokens.peek().is_none() {
+            first_blank_line.get_or_insert(i + 1);
+            return vec![];
+        }
+        if let Some(blank_line) = first_blank_line {
+            return vec![Err(TurtleError::BlankLine { line: blank_line })];
+        }
+
+        tokens
+            .map(|token| token.parse().map_err(|_| invalid_number(i + 1, token)))
+            .collect()
+    })
+}
+
+#[cfg(feature = "std")]
+fn invalid_number(line: usize, content: &str) -> TurtleError {
+    TurtleError::Parse {
+        line,
+        content: content.into(),
+    }
+}
+
+/// `f64` ordered with `total_cmp`, so that it can be stored in `SortedTunnel`.
+#[derive(Debug, Clone, Copy)]
+struct TotalOrderF64(f64);
+
+impl PartialEq for TotalOrderF64 {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for TotalOrderF64 {}
+
+impl PartialOrd for TotalOrderF64 {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for TotalOrderF64 {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.total_cmp(&other.0)
+    }
+}
+
+impl Add for TotalOrderF64 {
+    type Output = TotalOrderF64;
+
+    fn add(self, other: Self) -> Self::Output {
+        TotalOrderF64(self.0 + other.0)
+    }
+}
+
+/// Consumes `steps` until it finds a step which `is_safe` rejects for the current preceding fragment.
+fn find_critical_number<T: Ord + Add<Output = T> + Clone + Debug>(
+    steps_in_tunnel: impl Iterator<Item = T>,
+    tunnel_len: usize,
+    is_safe: impl FnMut(&SortedTunnel<T>, T) -> bool,
+) -> Option<IndexedStep<T>> {
+    collapses(Verdicts::new(steps_in_tunnel, tunnel_len, is_safe)).next()
+}
+
+/// Keeps only the steps of `verdicts` which collapsed the tunnel.
+fn collapses<T>(
+    verdicts: impl Iterator<Item = (IndexedStep<T>, bool)>,
+) -> impl Iterator<Item = IndexedStep<T>> {
+    verdicts.filter_map(|(step, is_safe)| (!is_safe).then_some(step))
+}
+
+/// Yields every step after the first `tunnel_len` steps with whether `is_safe` accepts it
+/// for the current preceding fragment, moving the preceding fragment forward in between.
+struct Verdicts<I, T, F>
+where
+    I: Iterator<Item = T>,
+    T: Ord + Add<Output = T> + Clone,
+{
+    steps_in_tunnel: Peekable<I>,
+    tunnel_len: usize,
+    is_safe: F,
+    /// Creates the preceding fragment from the first `tunnel_len` steps.
+    new_tunnel: fn(Vec<T>) -> SortedTunnel<T>,
+    sorted_tunnel: Option<SortedTunnel<T>>,
+    index: StepIndex,
+}
+
+impl<I, T, F> Verdicts<I, T, F>
+where
+    I: Iterator<Item = T>,
+    T: Ord + Add<Output = T> + Clone + Debug,
+    F: FnMut(&SortedTunnel<T>, T) -> bool,
+{
+    fn new(steps_in_tunnel: I, tunnel_len: usize, is_safe: F) -> Self {
+        Self::with_tunnel(steps_in_tunnel, tunnel_len, is_safe, SortedTunnel::new)
+    }
+
+    /// Works like [`Verdicts::new`], but the preceding fragment is created with `new_tunnel`.
+    fn with_tunnel(
+        steps_in_tunnel: I,
+        tunnel_len: usize,
+        is_safe: F,
+        new_tunnel: fn(Vec<T>) -> SortedTunnel<T>,
+    ) -> Self {
+        Verdicts {
+            steps_in_tunnel: steps_in_tunnel.peekable(),
+            tunnel_len,
+            is_safe,
+            new_tunnel,
+            sorted_tunnel: None,
+            // the first checked step comes right after the first tunnel_len steps
+            index: step_index(tunnel_len),
+        }
+    }
+}
+
+impl<I, T, F> Iterator for Verdicts<I, T, F>
+where
+    I: Iterator<Item = T>,
+    T: Ord + Add<Output = T> + Clone + Debug,
+    F: FnMut(&SortedTunnel<T>, T) -> bool,
+{
+    type Item = (IndexedStep<T>, bool);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let sorted_tunnel = match &mut self.sorted_tunnel {
+            Some(sorted_tunnel) => sorted_tunnel,
+            None => {
+                // first tunnel_len steps are removed from the iterator
+                let tunnel = self
+                    .steps_in_tunnel
+                    .by_ref()
+                    .take(self.tunnel_len)
+                    .collect();
+
+                // if the iterator is empty, there is nothing to check
+                self.steps_in_tunnel.peek()?;
+
+                self.sorted_tunnel.insert((self.new_tunnel)(tunnel))
+            }
+        };
+
+        if self.steps_in_tunnel.peek().is_none() {
+            log_event!(
+                debug,
+                "steps ended checked={}",
+                self.index - step_index(self.tunnel_len)
+            );
+        }
+        let step = self.steps_in_tunnel.next()?;
+        let index = self.index;
+        self.index += 1;
+
+        let is_safe = (self.is_safe)(sorted_tunnel, step.clone());
+        if !is_safe {
+            log_event!(info, "collapse detected index={index} step={step:?}");
+        }
+        sorted_tunnel.shift_right(step.clone());
+        Some((IndexedStep { index, step }, is_safe))
+    }
+}
+
+/// Low level utilities for examining tunnels for turtles
+pub mod tunnel_utils {
+    use alloc::collections::{btree_map, BTreeMap, BTreeSet, VecDeque};
+    use alloc::vec::Vec;
+    use core::cmp::Ordering;
+    use core::fmt::{self, Debug};
+    use core::ops::{Add, RangeInclusive, Sub};
+    use core::slice;
+
+    use crate::{DecodeError, IndexedStep};
+
+    /// Addition which reports an overflow instead of panicking or wrapping around.
+    pub trait CheckedAdd: Sized {
+        /// Returns the sum, or on overflow, on which side of the representable range the sum would land.
+        fn checked_add(&self, other: &Self) -> Result<Self, Ordering>;
+    }
+
+    macro_rules! impl_checked_add {
+        ($($t:ty)*) => ($(
+            impl CheckedAdd for $t {
+                fn checked_add(&self, other: &Self) -> Result<Self, Ordering> {
+                    // both operands have the same sign if the sum overflows
+                    <$t>::checked_add(*self, *other).ok_or(if *other > 0 {
+                        Ordering::Greater
+                    } else {
+                        Ordering::Less
+                    })
+                }
+            }
+        )*)
+    }
+
+    impl_checked_add! { u8 u16 u32 u64 u128 usize i8 i16 i32 i64 i128 isize }
+
+    /// Subtraction which reports an overflow instead of panicking or wrapping around.
+    pub trait CheckedSub: Sized {
+        /// Returns the difference, or on overflow, on which side of the representable range it would land.
+        fn checked_sub(&self, other: &Self) -> Result<Self, Ordering>;
+    }
+
+    macro_rules! impl_checked_sub {
+        ($($t:ty)*) => ($(
+            impl CheckedSub for $t {
+                fn checked_sub(&self, other: &Self) -> Result<Self, Ordering> {
+                    // the operands have different signs if the difference overflows
+                    <$t>::checked_sub(*self, *other).ok_or(if *self < *other {
+                        Ordering::Less
+                    } else {
+                        Ordering::Greater
+                    })
+                }
+            }
+        )*)
+    }
+
+    impl_checked_sub! { u8 u16 u32 u64 u128 usize i8 i16 i32 i64 i128 isize }
+
+    /// Structure holding the sorted steps of [`SortedTunnel`], chosen with [`SortedTunnel::with_backend`].
+    /// Both backends give the same results, they only differ in speed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use turtles::tunnel_utils::{Backend, SortedTunnel};
+    ///
+    /// let steps = [5, 4, 5, 9, 5, 14, 9];
+    /// let mut btree_tunnel = SortedTunnel::with_backend(steps[..3].to_vec(), Backend::BTreeMap);
+    /// let mut vec_tunnel = SortedTunnel::with_backend(steps[..3].to_vec(), Backend::SortedVec);
+    ///
+    /// for &step in &steps[3..] {
+    ///     assert_eq!(btree_tunnel.is_tunnel_safe(step), vec_tunnel.is_tunnel_safe(step));
+    ///     btree_tunnel.shift_right(step);
+    ///     vec_tunnel.shift_right(step);
+    ///     assert_eq!(btree_tunnel.window(), vec_tunnel.window());
+    /// }
+    /// ```
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+    pub enum Backend {
+        /// `BTreeMap` from every distinct step to its insertion counters, described in [`SortedTunnel`].
+        /// Adding or removing a step takes O(log d) for d distinct steps.
+        #[default]
+        BTreeMap,
+        /// `Vec` of distinct steps in ascending order with the number of times each of them appears.
+        /// Adding a new distinct step or removing its last occurrence shifts the vector, so it takes O(d)
+        /// for d distinct steps, but a step which is already present only updates its count.
+        /// It's faster for streams with very few distinct values, as they fit in a single small allocation.
+        SortedVec,
+    }
+
+    /// Sorted steps of the preceding fragment, stored as chosen by [`Backend`].
+    enum TunnelMap<T> {
+        BTreeMap(BTreeMap<T, VecDeque<usize>>),
+        SortedVec(Vec<(T, usize)>),
+    }
+
+    impl<T: Ord + Clone> TunnelMap<T> {
+        fn new(backend: Backend) -> Self {
+            match backend {
+                Backend::BTreeMap => TunnelMap::BTreeMap(BTreeMap::new()),
+                Backend::SortedVec => TunnelMap::SortedVec(Vec::new()),
+            }
+        }
+
+        /// Adds `step` as the newest step with insertion `counter`.
+        fn insert(&mut self, step: T, counter: usize) {
+            self.insert_with(step, counter, VecDeque::push_back);
+        }
+
+        /// Adds `step` as the oldest step with insertion `counter`.
+        fn insert_oldest(&mut self, step: T, counter: usize) {
+            self.insert_with(step, counter, VecDeque::push_front);
+        }
+
+        /// Adds `step`, placing its `counter` among the counters of equal steps with `push`.
+        fn insert_with(&mut self, step: T, counter: usize, push: fn(&mut VecDeque<usize>, usize)) {
+            match self {
+                TunnelMap::BTreeMap(map) => {
+                    map.entry(step)
+                        .and_modify(|counters| push(counters, counter))
+                        .or_insert(VecDeque::from([counter]));
+                }
+                TunnelMap::SortedVec(steps) => {
+                    match steps.binary_search_by(|(other, _)| other.cmp(&step)) {
+                        Ok(i) => steps[i].1 += 1,
+                        Err(i) => steps.insert(i, (step, 1)),
+                    }
+                }
+            }
+        }
+
+        /// Removes one occurrence of `step`, which has to be the oldest step with insertion `counter`.
+        fn remove(&mut self, step: &T, counter: usize) {
+            // counters of equal steps are ordered too, so the oldest one is at the front
+            self.remove_with(step, counter, VecDeque::pop_front);
+        }
+
+        /// Removes one occurrence of `step`, which has to be the newest step with insertion `counter`.
+        fn remove_newest(&mut self, step: &T, counter: usize) {
+            self.remove_with(step, counter, VecDeque::pop_back);
+        }
+
+        /// Removes one occurrence of `step`, taking its `counter` from the counters of equal steps with `pop`.
+        fn remove_with(
+            &mut self,
+            step: &T,
+            counter: usize,
+            pop: fn(&mut VecDeque<usize>) -> Option<usize>,
+        ) {
+            match self {
+                TunnelMap::BTreeMap(map) => {
+                    let counters = map.get_mut(step).unwrap();
+                    let removed_counter = pop(counters);
+                    debug_assert_eq!(removed_counter, Some(counter));
+                    if counters.is_empty() {
+                        map.remove(step);
+                    }
+                }
+                TunnelMap::SortedVec(steps) => {
+                    let i = steps
+                        .binary_search_by(|(other, _)| other.cmp(step))
+                        .unwrap();
+                    steps[i].1 -= 1;
+                    if steps[i].1 == 0 {
+                        steps.remove(i);
+                    }
+                }
+            }
+        }
+
+        fn clear(&mut self) {
+            match self {
+                TunnelMap::BTreeMap(map) => map.clear(),
+                TunnelMap::SortedVec(steps) => steps.clear(),
+            }
+        }
+
+        fn len(&self) -> usize {
+            match self {
+                TunnelMap::BTreeMap(map) => map.len(),
+                TunnelMap::SortedVec(steps) => steps.len(),
+            }
+        }
+
+        fn contains_key(&self, step: &T) -> bool {
+            match self {
+                TunnelMap::BTreeMap(map) => map.contains_key(step),
+                TunnelMap::SortedVec(steps) => {
+                    steps.binary_search_by(|(other, _)| other.cmp(step)).is_ok()
+                }
+            }
+        }
+
+        /// Iterates over distinct steps in ascending order with the number of times each of them appears.
+        fn iter(&self) -> TunnelMapIter<'_, T> {
+            match self {
+                TunnelMap::BTreeMap(map) => TunnelMapIter::BTreeMap(map.iter()),
+                TunnelMap::SortedVec(steps) => TunnelMapIter::SortedVec(steps.iter()),
+            }
+        }
+
+        /// Returns all steps in ascending order, including duplicates.
+        fn into_sorted_vec(self) -> Vec<T> {
+            match self {
+                TunnelMap::BTreeMap(map) => map
+                    .into_iter()
+                    .flat_map(|(step, counters)| core::iter::repeat_n(step, counters.len()))
+                    .collect(),
+                TunnelMap::SortedVec(steps) => steps
+                    .into_iter()
+                    .flat_map(|(step, count)| core::iter::repeat_n(step, count))
+                    .collect(),
+            }
+        }
+    }
+
+    /// Iterator returned by [`TunnelMap::iter`].
+    enum TunnelMapIter<'a, T> {
+        BTreeMap(btree_map::Iter<'a, T, VecDeque<usize>>),
+        SortedVec(slice::Iter<'a, (T, usize)>),
+    }
+
+    impl<'a, T> Iterator for TunnelMapIter<'a, T> {
+        type Item = (&'a T, usize);
+
+        fn next(&mut self) -> Option<Self::Item> {
+            match self {
+                TunnelMapIter::BTreeMap(iter) => {
+                    iter.next().map(|(step, counters)| (step, counters.len()))
+                }
+                TunnelMapIter::SortedVec(iter) => iter.next().map(|(step, count)| (step, *count)),
+            }
+        }
+    }
+
+    impl<T> DoubleEndedIterator for TunnelMapIter<'_, T> {
+        fn next_back(&mut self) -> Option<Self::Item> {
+            match self {
+                TunnelMapIter::BTreeMap(iter) => iter
+                    .next_back()
+                    .map(|(step, counters)| (step, counters.len())),
+                TunnelMapIter::SortedVec(iter) => {
+                    iter.next_back().map(|(step, count)| (step, *count))
+                }
+            }
+        }
+    }
+
+    /// This trait allows us to handle inserting duplicate keys to the BTreeMap.
+    trait AddDuplicate<T>
+    where
+        T: Ord + Add<Output = T> + Clone,
+    {
+        fn add_duplicate(&mut self, step: T, counter: usize);
+    }
+
+    /// Holds information about preceding fragment of the tunnel.
+    ///
+    /// `BTreeMap` is used by default because it's sorted and because of its fast lookup times,
+    /// and [`SortedTunnel::with_backend`] can choose another [`Backend`].
+    /// It cannot store duplicate keys, so the underlying queue represents how many keys are present.
+    /// `usize` values are insertion counters, which only grow as the tunnel moves forward,
+    /// so the age of an element is its counter minus the counter of the oldest element.
+    /// This way steps never have to be renumbered when the oldest one is removed.
+    ///
+    /// This is efficient because checking sums of elements is the majority of operations conducted in the process,
+    /// and a sorted map can be walked from both ends to find a sum, no matter if the elements are negative.
+    /// Also, we will only remove elements from the start of the queue and append at its end,
+    /// so `VecDeque` is also appropriate. Another `VecDeque` keeps the steps in the order they were added,
+    /// so the oldest step can be found without searching the map.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use turtles::tunnel_utils::SortedTunnel;
+    ///
+    /// let mut sorted_tunnel = SortedTunnel::new(vec![5, 4, 7]);
+    ///
+    /// assert!(sorted_tunnel.is_tunnel_safe(9));
+    /// sorted_tunnel.shift_right(9);
+    ///
+    /// // 5 is no longer in the preceding fragment
+    /// assert!(!sorted_tunnel.is_tunnel_safe(14));
+    /// assert!(sorted_tunnel.is_tunnel_safe(16));
+    /// ```
+    pub struct SortedTunnel<T>
+    where
+        T: Ord + Add<Output = T> + Clone,
+    {
+        tunnel_map: TunnelMap<T>,
+        steps_by_age: VecDeque<T>,
+        /// Insertion counter of the oldest step.
+        oldest_counter: usize,
+        /// Age of the newest step, so the maximum age in the preceding fragment.
+        /// It's one less than the number of steps the fragment holds, not the number itself,
+        /// and it's 0 for an empty fragment as well.
+        tunnel_length: usize,
+        /// Whether an element appearing once can be added to itself.
+        allow_self_pair: bool,
+        /// Whether a step equal to a preceding element is safe on its own.
+        allow_repeat: bool,
+        /// Number of steps [`shift_right`](SortedTunnel::shift_right) still appends
+        /// before it starts to remove the oldest one, so how much an under-filled window lacks.
+        missing_steps: usize,
+        /// Number of steps appended into the room of an under-filled window since it was created,
+        /// which [`shift_left`](SortedTunnel::shift_left) takes back once there's no removal left to undo.
+        appended_steps: usize,
+        /// Total of the steps, if [`track_sum`](SortedTunnel::track_sum) was called.
+        running_sum: Option<RunningSum<T>>,
+    }
+
+    /// Total of the steps in the preceding fragment, kept up to date as steps are added and removed.
+    ///
+    /// Non-negative and negative steps are added up separately, so removing a step never overflows,
+    /// and each total is `None` when it doesn't fit in `T`. The operations come from
+    /// [`SortedTunnel::track_sum`], as the tunnel itself doesn't require them.
+    struct RunningSum<T> {
+        zero: T,
+        non_negative: Option<T>,
+        negative: Option<T>,
+        checked_add: fn(&T, &T) -> Result<T, Ordering>,
+        sub: fn(T, T) -> T,
+    }
+
+    impl<T: Ord + Clone> RunningSum<T> {
+        fn add(&mut self, step: &T) {
+            let total = if *step >= self.zero {
+                &mut self.non_negative
+            } else {
+                &mut self.negative
+            };
+            *total = total
+                .as_ref()
+                .and_then(|total| (self.checked_add)(total, step).ok());
+        }
+
+        /// Removes `step` from the totals, where `remaining` are the steps left in the fragment.
+        fn remove(&mut self, step: &T, remaining: &VecDeque<T>) {
+            let is_non_negative = *step >= self.zero;
+            let total = if is_non_negative {
+                &mut self.non_negative
+            } else {
+                &mut self.negative
+            };
+            *total = match total.take() {
+                Some(total) => Some((self.sub)(total, step.clone())),
+                // the total didn't fit, but it may without the removed step
+                None => Self::add_up(&self.zero, self.checked_add, remaining, is_non_negative),
+            };
+        }
+
+        /// Adds up the non-negative or the negative `steps`, which overflows only if their total doesn't fit.
+        fn add_up<'a>(
+            zero: &T,
+            checked_add: fn(&T, &T) -> Result<T, Ordering>,
+            steps: impl IntoIterator<Item = &'a T>,
+            non_negative: bool,
+        ) -> Option<T>
+        where
+            T: 'a,
+        {
+            steps
+                .into_iter()
+                .filter(|step| (*step >= zero) == non_negative)
+                .try_fold(zero.clone(), |total, step| checked_add(&total, step).ok())
+        }
+
+        fn total(&self) -> Option<T> {
+            // a non-negative and a negative total never overflow together
+            (self.checked_add)(self.non_negative.as_ref()?, self.negative.as_ref()?).ok()
+        }
+    }
+
+    impl<T> AddDuplicate<T> for SortedTunnel<T>
+    where
+        T: Ord + Add<Output = T> + Clone,
+    {
+        fn add_duplicate(&mut self, step: T, counter: usize) {
+            if let Some(running_sum) = &mut self.running_sum {
+                running_sum.add(&step);
+            }
+            self.tunnel_map.insert(step.clone(), counter);
+            self.steps_by_age.push_back(step);
+        }
+    }
+
+    impl<T: Ord + Add<Output = T> + Clone + Debug> SortedTunnel<T> {
+        /// Creates a tunnel from its preceding fragment, ordered from the oldest to the newest step.
+        ///
+        /// The fragment can be empty, in which case every step collapses the tunnel.
+        ///
+        /// # Examples
+        ///
+        /// ```
+        /// use turtles::tunnel_utils::SortedTunnel;
+        ///
+        /// let mut sorted_tunnel = SortedTunnel::new(vec![]);
+        ///
+        /// assert!(sorted_tunnel.is_empty());
+        /// assert!(!sorted_tunnel.is_tunnel_safe(0));
+        ///
+        /// // there's no room for new steps either
+        /// sorted_tunnel.shift_right(5);
+        /// assert!(sorted_tunnel.is_empty());
+        ///
+        ///
+        /// // a single element is replaced by every shift, and no step is safe after it
+        /// let mut sorted_tunnel = SortedTunnel::new(vec![5]);
+        ///
+        /// assert!(!sorted_tunnel.is_tunnel_safe(10));
+        /// sorted_tunnel.shift_right(7);
+        /// assert_eq!(sorted_tunnel.window(), vec![7]);
+        /// assert!(!sorted_tunnel.is_tunnel_safe(14));
+        /// assert!(sorted_tunnel.allow_self_pair(true).is_tunnel_safe(14));
+        /// ```
+        pub fn new(tunnel: Vec<T>) -> SortedTunnel<T> {
+            Self::with_backend(tunnel, Backend::default())
+        }
+
+        /// Creates a preceding fragment that slides over `window_len` steps, starting with `initial`.
+        ///
+        /// `window_len` is the eviction threshold: while the fragment holds fewer steps,
+        /// [`shift_right`](SortedTunnel::shift_right) appends the new step without removing any,
+        /// and only once it's full does every shift remove the oldest one.
+        /// `initial` may contain fewer than `window_len` steps; if it contains more,
+        /// only the newest `window_len` of them are kept.
+        /// [`SortedTunnel::new`] is the case where `initial` fills the whole window.
+        ///
+        /// [`push`](SortedTunnel::push), and so [`merge`](SortedTunnel::merge) and [`Extend`], fill the room left as well,
+        /// and only raise the threshold once the window is full.
+        /// [`shift_left`](SortedTunnel::shift_left) takes an appended step back, leaving the room for it again.
+        ///
+        /// # Examples
+        ///
+        /// ```
+        /// use turtles::tunnel_utils::SortedTunnel;
+        ///
+        /// let mut sorted_tunnel = SortedTunnel::with_capacity(3, vec![5]);
+        /// assert_eq!(sorted_tunnel.len(), 1);
+        ///
+        /// sorted_tunnel.shift_right(4);
+        /// sorted_tunnel.shift_right(9);
+        /// assert_eq!(sorted_tunnel.window(), vec![4, 5, 9]);
+        /// assert_eq!(sorted_tunnel.oldest(), Some(5));
+        ///
+        /// // the window is full now, so the oldest step goes
+        /// sorted_tunnel.shift_right(1);
+        /// assert_eq!(sorted_tunnel.window(), vec![1, 4, 9]);
+        /// assert_eq!(sorted_tunnel.oldest(), Some(4));
+        /// assert!(sorted_tunnel.is_tunnel_safe(10));
+        /// ```
+        ///
+        /// Steps added in other ways count towards the window too, and undoing a shift gives the room back:
+        ///
+        /// ```
+        /// use turtles::tunnel_utils::SortedTunnel;
+        ///
+        /// let mut sorted_tunnel = SortedTunnel::with_capacity(3, vec![5]);
+        /// sorted_tunnel.extend([7]);
+        /// sorted_tunnel.shift_right_many([1, 2, 3]);
+        /// assert_eq!(sorted_tunnel.window(), vec![1, 2, 3]);
+        ///
+        /// let mut sorted_tunnel = SortedTunnel::with_capacity(3, vec![5]);
+        /// sorted_tunnel.shift_right(4);
+        /// sorted_tunnel.shift_left(99);
+        /// assert_eq!(sorted_tunnel.window(), vec![5]);
+        ///
+        /// // the window still takes 2 more steps before it slides
+        /// sorted_tunnel.shift_right_many([4, 9, 1]);
+        /// assert_eq!(sorted_tunnel.window(), vec![1, 4, 9]);
+        /// ```
+        ///
+        /// An empty `initial` is filled by the first shifts, and a longer one is cut down to the window:
+        ///
+        /// ```
+        /// use turtles::tunnel_utils::SortedTunnel;
+        ///
+        /// let mut sorted_tunnel = SortedTunnel::with_capacity(2, vec![]);
+        /// sorted_tunnel.shift_right(7);
+        /// assert_eq!(sorted_tunnel.window(), vec![7]);
+        /// sorted_tunnel.shift_right_many([8, 3]);
+        /// assert_eq!(sorted_tunnel.window(), vec![3, 8]);
+        ///
+        /// let sorted_tunnel = SortedTunnel::with_capacity(2, vec![1, 2, 3]);
+        /// assert_eq!(sorted_tunnel.window(), vec![2, 3]);
+        /// assert_eq!(sorted_tunnel.oldest(), Some(2));
+        ///
+        /// let mut sorted_tunnel = SortedTunnel::with_capacity(0, vec![1, 2]);
+        /// sorted_tunnel.shift_right(3);
+        /// assert!(sorted_tunnel.is_empty());
+        /// ```
+        pub fn with_capacity(
+            window_len: usize,
+            initial: impl IntoIterator<Item = T>,
+        ) -> SortedTunnel<T> {
+            let mut sorted_tunnel = Self::new(Vec::new());
+            sorted_tunnel.missing_steps = window_len;
+            sorted_tunnel.shift_right_many(initial);
+            // the initial steps aren't shifts to be undone
+            sorted_tunnel.appended_steps = 0;
+            sorted_tunnel
+        }
+
+        /// Works like [`SortedTunnel::new`], but the steps are stored in the chosen [`Backend`].
+        pub fn with_backend(tunnel: Vec<T>, backend: Backend) -> SortedTunnel<T> {
+            let mut sorted_tunnel = SortedTunnel {
+                tunnel_map: TunnelMap::new(backend),
+                steps_by_age: VecDeque::with_capacity(tunnel.len()),
+                oldest_counter: 0,
+                tunnel_length: tunnel.len().saturating_sub(1),
+                allow_self_pair: false,
+                allow_repeat: false,
+                missing_steps: 0,
+                appended_steps: 0,
+                running_sum: None,
+            };
+            for (counter, step) in tunnel.into_iter().enumerate() {
+                sorted_tunnel.add_duplicate(step, counter);
+            }
+            log_event!(
+                debug,
+                "tunnel created len={} distinct={} backend={backend:?}",
+                sorted_tunnel.len(),
+                sorted_tunnel.distinct_count()
+            );
+            sorted_tunnel
+        }
+
+        /// Replaces the whole preceding fragment, like creating it again with [`SortedTunnel::new`],
+        /// but reusing the memory already allocated for the steps where possible.
+        /// [`allow_self_pair`](SortedTunnel::allow_self_pair) and [`allow_repeat`](SortedTunnel::allow_repeat) stay as they were.
+        ///
+        /// # Examples
+        ///
+        /// ```
+        /// use turtles::tunnel_utils::SortedTunnel;
+        ///
+        /// let mut sorted_tunnel = SortedTunnel::new(vec![1, 2, 3, 4]);
+        /// sorted_tunnel.shift_right(5);
+        /// sorted_tunnel.reset(vec![5, 4, 7]);
+        /// let mut fresh_tunnel = SortedTunnel::new(vec![5, 4, 7]);
+        ///
+        /// for step in [9, 14, 16] {
+        ///     assert_eq!(sorted_tunnel.is_tunnel_safe(step), fresh_tunnel.is_tunnel_safe(step));
+        ///     sorted_tunnel.shift_right(step);
+        ///     fresh_tunnel.shift_right(step);
+        ///     assert_eq!(sorted_tunnel.window(), fresh_tunnel.window());
+        /// }
+        /// assert_eq!(sorted_tunnel.len(), fresh_tunnel.len());
+        /// assert_eq!(sorted_tunnel.oldest(), fresh_tunnel.oldest());
+        /// ```
+        pub fn reset(&mut self, tunnel: Vec<T>) {
+            self.tunnel_map.clear();
+            self.steps_by_age.clear();
+            self.oldest_counter = 0;
+            self.tunnel_length = tunnel.len().saturating_sub(1);
+            self.missing_steps = 0;
+            self.appended_steps = 0;
+            if let Some(running_sum) = &mut self.running_sum {
+                running_sum.non_negative = Some(running_sum.zero.clone());
+                running_sum.negative = Some(running_sum.zero.clone());
+            }
+            for (counter, step) in tunnel.into_iter().enumerate() {
+                self.add_duplicate(step, counter);
+            }
+        }
+
+        /// Removes the oldest step in preceding fragment, if there is any.
+        fn remove_oldest_step(&mut self) {
+            debug_assert!(
+                !self.is_empty(),
+                "There was no oldest step in SortedTunnel before removal"
+            );
+            let Some(step) = self.steps_by_age.pop_front() else {
                 return;
+            };
+
+            self.tunnel_map.remove(&step, self.oldest_counter);
+            if let Some(running_sum) = &mut self.running_sum {
+                running_sum.remove(&step, &self.steps_by_age);
             }
-            panic!("There was no oldest step in SortedTunnel before removal");
+
+            self.oldest_counter = self.oldest_counter.wrapping_add(1);
         }
 
         /// Replaces the oldest step from preceding tunnel fragment with a new step.
+        /// An empty fragment stays empty, as there's no oldest step to replace.
+        ///
+        /// # Examples
+        ///
+        /// ```
+        /// use turtles::tunnel_utils::SortedTunnel;
+        ///
+        /// let mut sorted_tunnel = SortedTunnel::new(vec![5, 4]);
+        /// sorted_tunnel.shift_right(9);
+        ///
+        /// assert_eq!(sorted_tunnel.window(), vec![4, 9]);
+        ///
+        ///
+        /// let mut sorted_tunnel = SortedTunnel::new(vec![]);
+        /// sorted_tunnel.shift_right(9);
+        ///
+        /// assert!(sorted_tunnel.is_empty());
+        /// assert_eq!(sorted_tunnel.oldest(), None);
+        /// ```
         pub fn shift_right(&mut self, new_step: T) {
+            if self.missing_steps > 0 {
+                log_event!(trace, "step shifted added={new_step:?}");
+                self.push(new_step);
+                return;
+            }
+            if self.is_empty() {
+                return;
+            }
+            log_event!(trace, "step shifted added={new_step:?}");
             self.remove_oldest_step();
-            self.add_duplicate(new_step, self.tunnel_length);
+            self.add_duplicate(
+                new_step,
+                self.oldest_counter.wrapping_add(self.tunnel_length),
+            );
+        }
+
+        /// Applies [`shift_right`](SortedTunnel::shift_right) for every step of `new_steps`, in their order.
+        ///
+        /// Ages come from insertion counters, so no step is renumbered by a shift,
+        /// and each of them only removes one step and adds another.
+        ///
+        /// # Examples
+        ///
+        /// ```
+        /// use turtles::tunnel_utils::SortedTunnel;
+        ///
+        /// let mut sorted_tunnel = SortedTunnel::new(vec![5, 4, 7]);
+        /// sorted_tunnel.shift_right_many([9, 4, 13, 4]);
+        ///
+        /// let mut shifted_one_by_one = SortedTunnel::new(vec![5, 4, 7]);
+        /// for step in [9, 4, 13, 4] {
+        ///     shifted_one_by_one.shift_right(step);
+        /// }
+        ///
+        /// assert_eq!(format!("{sorted_tunnel:?}"), format!("{shifted_one_by_one:?}"));
+        /// assert_eq!(sorted_tunnel.window(), vec![4, 4, 13]);
+        /// ```
+        pub fn shift_right_many(&mut self, new_steps: impl IntoIterator<Item = T>) {
+            for new_step in new_steps {
+                self.shift_right(new_step);
+            }
+        }
+
+        /// Checks every step of `steps` against the preceding fragment, shifting it right after each safe one,
+        /// and returns the first step which collapses the tunnel, or `None` if every step is safe.
+        ///
+        /// The index of the collapse counts from the first step of `steps`, not from the start of the stream.
+        /// The fragment left in `self` holds the steps up to the last safe one, and the collapsing step isn't added,
+        /// so processing can be resumed later by calling this again with the remaining steps.
+        ///
+        /// # Examples
+        ///
+        /// ```
+        /// use turtles::tunnel_utils::SortedTunnel;
+        /// use turtles::IndexedStep;
+        ///
+        /// let mut sorted_tunnel = SortedTunnel::new(vec![1, 2, 3]);
+        ///
+        /// assert_eq!(sorted_tunnel.next_collapse([5, 8].into_iter()), None);
+        /// assert_eq!(sorted_tunnel.window(), vec![3, 5, 8]);
+        ///
+        /// // resuming from the saved fragment, without seeding it again
+        /// let collapse = sorted_tunnel.next_collapse([13, 20, 21].into_iter());
+        /// assert_eq!(collapse, Some(IndexedStep { step: 20, index: 1 }));
+        /// assert_eq!(sorted_tunnel.window(), vec![5, 8, 13]);
+        /// ```
+        pub fn next_collapse(&mut self, steps: impl Iterator<Item = T>) -> Option<IndexedStep<T>> {
+            for (index, step) in (0..).zip(steps) {
+                if !self.is_tunnel_safe(step.clone()) {
+                    return Some(IndexedStep { step, index });
+                }
+                self.shift_right(step);
+            }
+            None
+        }
+
+        /// Undoes [`shift_right`](SortedTunnel::shift_right): removes the newest step
+        /// and puts `restored_oldest` back as the oldest one. An empty fragment stays empty.
+        ///
+        /// A shift which appended a step to an under-filled window (see [`with_capacity`](SortedTunnel::with_capacity))
+        /// didn't remove any, so undoing it only removes the newest step and `restored_oldest` is ignored.
+        /// Shifts are undone from the latest one, so that's the case once every removal was undone.
+        ///
+        /// The tunnel doesn't remember the steps it removed, so `restored_oldest` has to be
+        /// the step removed by the shift being undone. Any other value is accepted too,
+        /// but then the fragment won't be the same as before the shift.
+        ///
+        /// # Examples
+        ///
+        /// ```
+        /// use turtles::tunnel_utils::SortedTunnel;
+        ///
+        /// let mut sorted_tunnel = SortedTunnel::new(vec![5, 4, 7]);
+        /// let oldest = sorted_tunnel.oldest().unwrap();
+        /// sorted_tunnel.shift_right(9);
+        /// sorted_tunnel.shift_left(oldest);
+        ///
+        /// assert_eq!(format!("{sorted_tunnel:?}"), format!("{:?}", SortedTunnel::new(vec![5, 4, 7])));
+        /// assert!(sorted_tunnel.is_tunnel_safe(12));
+        ///
+        /// // the restored step is the next one to be removed again
+        /// sorted_tunnel.shift_right(12);
+        /// assert_eq!(sorted_tunnel.window(), vec![4, 7, 12]);
+        /// ```
+        pub fn shift_left(&mut self, restored_oldest: T) {
+            let Some(newest) = self.steps_by_age.pop_back() else {
+                return;
+            };
+            self.tunnel_map.remove_newest(
+                &newest,
+                self.oldest_counter.wrapping_add(self.tunnel_length),
+            );
+            if let Some(running_sum) = &mut self.running_sum {
+                running_sum.remove(&newest, &self.steps_by_age);
+            }
+
+            // appends happen before the first removal, while the oldest counter hasn't moved yet
+            if self.appended_steps > 0 && self.oldest_counter == 0 {
+                self.appended_steps -= 1;
+                self.missing_steps += 1;
+                self.tunnel_length = self.tunnel_length.saturating_sub(1);
+                return;
+            }
+
+            if let Some(running_sum) = &mut self.running_sum {
+                running_sum.add(&restored_oldest);
+            }
+
+            self.oldest_counter = self.oldest_counter.wrapping_sub(1);
+            self.tunnel_map
+                .insert_oldest(restored_oldest.clone(), self.oldest_counter);
+            self.steps_by_age.push_front(restored_oldest);
+        }
+
+        /// Sets if an element can be added to itself even if it appears only once, `false` by default.
+        /// Every check of the tunnel respects it, and with more than 2 addends
+        /// it allows to use every element any number of times.
+        ///
+        /// # Examples
+        ///
+        /// ```
+        /// use turtles::tunnel_utils::SortedTunnel;
+        ///
+        /// assert!(!SortedTunnel::new(vec![5]).is_tunnel_safe(10));
+        ///
+        /// let sorted_tunnel = SortedTunnel::new(vec![5]).allow_self_pair(true);
+        ///
+        /// assert!(sorted_tunnel.is_tunnel_safe(10));
+        /// assert_eq!(sorted_tunnel.safe_pair(10), Some((5, 5)));
+        /// assert!(sorted_tunnel.is_tunnel_safe_k(15, 3));
+        /// ```
+        pub fn allow_self_pair(mut self, allow_self_pair: bool) -> Self {
+            self.allow_self_pair = allow_self_pair;
+            self
+        }
+
+        /// Sets if a step equal to any preceding element keeps the tunnel safe, `false` by default.
+        ///
+        /// It only changes [`is_tunnel_safe`](SortedTunnel::is_tunnel_safe) and [`is_tunnel_safe_k`](SortedTunnel::is_tunnel_safe_k),
+        /// so the repeat needn't be a sum of elements, unlike with [`allow_self_pair`](SortedTunnel::allow_self_pair),
+        /// and the pair searches don't report it.
+        ///
+        /// # Examples
+        ///
+        /// ```
+        /// use turtles::tunnel_utils::SortedTunnel;
+        ///
+        /// assert!(!SortedTunnel::new(vec![5, 4]).is_tunnel_safe(5));
+        ///
+        /// let sorted_tunnel = SortedTunnel::new(vec![5, 4]).allow_repeat(true);
+        ///
+        /// assert!(sorted_tunnel.is_tunnel_safe(5));
+        /// assert!(sorted_tunnel.is_tunnel_safe(9));
+        /// assert!(!sorted_tunnel.is_tunnel_safe(10));
+        /// assert_eq!(sorted_tunnel.safe_pair(5), None);
+        /// ```
+        pub fn allow_repeat(mut self, allow_repeat: bool) -> Self {
+            self.allow_repeat = allow_repeat;
+            self
+        }
+
+        /// Keeps the total of the steps up to date as they're added and removed,
+        /// so that [`window_sum`](SortedTunnel::window_sum) takes O(1).
+        ///
+        /// Non-negative and negative steps are added up separately, and each addition is checked.
+        /// While either of these totals doesn't fit in `T`, removing a step of its sign adds it up again in O(n),
+        /// as it may fit without the removed step. Otherwise every addition and removal takes O(1).
+        ///
+        /// # Examples
+        ///
+        /// ```
+        /// use turtles::tunnel_utils::SortedTunnel;
+        ///
+        /// let mut sorted_tunnel = SortedTunnel::new(vec![5u64, 4, 7]).track_sum();
+        /// assert_eq!(sorted_tunnel.window_sum(), Some(16));
+        ///
+        /// for step in 0..1000 {
+        ///     sorted_tunnel.shift_right(step * 7 % 13);
+        ///     assert_eq!(sorted_tunnel.window_sum(), Some(sorted_tunnel.window().iter().sum()));
+        /// }
+        ///
+        ///
+        /// // the total doesn't fit in `u8` until 200 is removed
+        /// let mut sorted_tunnel = SortedTunnel::new(vec![200u8, 100]).track_sum();
+        /// assert_eq!(sorted_tunnel.window_sum(), None);
+        ///
+        /// sorted_tunnel.shift_right(50);
+        /// assert_eq!(sorted_tunnel.window_sum(), Some(150));
+        /// ```
+        pub fn track_sum(mut self) -> Self
+        where
+            T: CheckedAdd + Sub<Output = T> + Default,
+        {
+            let zero = T::default();
+            let checked_add: fn(&T, &T) -> Result<T, Ordering> = T::checked_add;
+            self.running_sum = Some(RunningSum {
+                non_negative: RunningSum::add_up(&zero, checked_add, &self.steps_by_age, true),
+                negative: RunningSum::add_up(&zero, checked_add, &self.steps_by_age, false),
+                zero,
+                checked_add,
+                sub: |total, step| total - step,
+            });
+            self
+        }
+
+        /// Returns the total of all the steps in the preceding fragment,
+        /// or `None` if it doesn't fit in `T` (or the non-negative or the negative steps alone don't).
+        /// It's 0, so `T::default()`, for an empty fragment.
+        ///
+        /// It takes O(1) after [`track_sum`](SortedTunnel::track_sum), and otherwise adds up the steps in O(n).
+        ///
+        /// # Examples
+        ///
+        /// ```
+        /// use turtles::tunnel_utils::SortedTunnel;
+        ///
+        /// let mut sorted_tunnel = SortedTunnel::new(vec![-100i8, 100, 20]);
+        /// assert_eq!(sorted_tunnel.window_sum(), Some(20));
+        ///
+        /// sorted_tunnel.shift_right(-100);
+        /// assert_eq!(sorted_tunnel.window_sum(), Some(20));
+        ///
+        /// // the negative steps add up to -200
+        /// sorted_tunnel.shift_right(-100);
+        /// assert_eq!(sorted_tunnel.window_sum(), None);
+        ///
+        /// assert_eq!(SortedTunnel::<i8>::new(vec![]).window_sum(), Some(0));
+        /// ```
+        pub fn window_sum(&self) -> Option<T>
+        where
+            T: CheckedAdd + Default,
+        {
+            if let Some(running_sum) = &self.running_sum {
+                return running_sum.total();
+            }
+            let zero = T::default();
+            let checked_add: fn(&T, &T) -> Result<T, Ordering> = T::checked_add;
+            let non_negative = RunningSum::add_up(&zero, checked_add, &self.steps_by_age, true)?;
+            let negative = RunningSum::add_up(&zero, checked_add, &self.steps_by_age, false)?;
+            checked_add(&non_negative, &negative).ok()
+        }
+
+        /// Appends a step as newer than every step in the preceding fragment, without removing the oldest one,
+        /// so the fragment grows by one.
+        ///
+        /// In an under-filled window (see [`with_capacity`](SortedTunnel::with_capacity)) the step takes up
+        /// some of the room left, and otherwise it raises the eviction threshold by one.
+        ///
+        /// # Examples
+        ///
+        /// ```
+        /// use turtles::tunnel_utils::SortedTunnel;
+        ///
+        /// let mut sorted_tunnel = SortedTunnel::new(vec![5, 4]);
+        /// sorted_tunnel.push(7);
+        ///
+        /// assert_eq!(sorted_tunnel.window(), vec![4, 5, 7]);
+        /// assert_eq!(sorted_tunnel.newest(), Some(7));
+        /// ```
+        pub fn push(&mut self, new_step: T) {
+            if self.missing_steps > 0 {
+                self.missing_steps -= 1;
+                self.appended_steps += 1;
+            }
+            // the first step of an empty fragment is both the oldest and the newest one
+            if !self.is_empty() {
+                self.tunnel_length += 1;
+            }
+            self.add_duplicate(
+                new_step,
+                self.oldest_counter.wrapping_add(self.tunnel_length),
+            );
+        }
+
+        /// Appends every step of `other` as newer than every step in the preceding fragment,
+        /// keeping their order, like [`push`](SortedTunnel::push) called for each of them.
+        ///
+        /// No step is removed, so the combined fragment holds `self.len() + other.len()` steps.
+        /// [`allow_self_pair`](SortedTunnel::allow_self_pair) and [`allow_repeat`](SortedTunnel::allow_repeat) of `self` stay as they were.
+        ///
+        /// # Examples
+        ///
+        /// ```
+        /// use turtles::tunnel_utils::SortedTunnel;
+        ///
+        /// let mut sorted_tunnel = SortedTunnel::new(vec![5, 4]);
+        /// sorted_tunnel.merge(SortedTunnel::new(vec![7, 4, 9]));
+        /// let mut sequential_tunnel = SortedTunnel::new(vec![5, 4, 7, 4, 9]);
+        ///
+        /// assert_eq!(sorted_tunnel.len(), 5);
+        /// for step in [13, 8, 20] {
+        ///     assert_eq!(sorted_tunnel.is_tunnel_safe(step), sequential_tunnel.is_tunnel_safe(step));
+        ///     sorted_tunnel.shift_right(step);
+        ///     sequential_tunnel.shift_right(step);
+        ///     assert_eq!(sorted_tunnel.window(), sequential_tunnel.window());
+        /// }
+        /// assert_eq!(sorted_tunnel.oldest(), sequential_tunnel.oldest());
+        /// ```
+        pub fn merge(&mut self, other: SortedTunnel<T>) {
+            for step in other.steps_by_age {
+                self.push(step);
+            }
+        }
+
+        /// Returns the number of steps in the preceding fragment, counting every duplicate.
+        ///
+        /// # Examples
+        ///
+        /// ```
+        /// use turtles::tunnel_utils::SortedTunnel;
+        ///
+        /// let sorted_tunnel = SortedTunnel::new(vec![5, 4, 5, 5]);
+        ///
+        /// assert_eq!(sorted_tunnel.len(), 4);
+        /// assert!(!sorted_tunnel.is_empty());
+        /// ```
+        pub fn len(&self) -> usize {
+            self.steps_by_age.len()
+        }
+
+        /// Returns the number of distinct steps in the preceding fragment, so duplicates are counted once.
+        ///
+        /// # Examples
+        ///
+        /// ```
+        /// use turtles::tunnel_utils::SortedTunnel;
+        ///
+        /// let sorted_tunnel = SortedTunnel::new(vec![5, 4, 5, 7, 4, 5]);
+        ///
+        /// assert_eq!(sorted_tunnel.distinct_count(), 3);
+        /// assert_eq!(sorted_tunnel.len(), 6);
+        /// ```
+        pub fn distinct_count(&self) -> usize {
+            self.tunnel_map.len()
+        }
+
+        /// Checks if no step appears more than once in the preceding fragment, which is true for an empty one too.
+        ///
+        /// # Examples
+        ///
+        /// ```
+        /// use turtles::tunnel_utils::SortedTunnel;
+        ///
+        /// let mut sorted_tunnel = SortedTunnel::new(vec![5, 4, 7]);
+        ///
+        /// assert!(sorted_tunnel.is_all_distinct());
+        /// sorted_tunnel.shift_right(4);
+        /// assert!(!sorted_tunnel.is_all_distinct());
+        /// ```
+        pub fn is_all_distinct(&self) -> bool {
+            // every duplicate is counted by `len`, but only once by `distinct_count`
+            self.distinct_count() == self.len()
+        }
+
+        /// Checks if there are no steps in the preceding fragment.
+        pub fn is_empty(&self) -> bool {
+            self.steps_by_age.is_empty()
+        }
+
+        /// Returns the oldest step in the preceding fragment, which is the next one to be removed.
+        ///
+        /// # Examples
+        ///
+        /// ```
+        /// use turtles::tunnel_utils::SortedTunnel;
+        ///
+        /// let mut sorted_tunnel = SortedTunnel::new(vec![5, 4, 7]);
+        /// sorted_tunnel.shift_right(9);
+        ///
+        /// assert_eq!(sorted_tunnel.oldest(), Some(4));
+        /// assert_eq!(sorted_tunnel.newest(), Some(9));
+        ///
+        /// let sorted_tunnel = SortedTunnel::<u128>::new(vec![]);
+        ///
+        /// assert_eq!(sorted_tunnel.oldest(), None);
+        /// assert_eq!(sorted_tunnel.newest(), None);
+        /// ```
+        pub fn oldest(&self) -> Option<T> {
+            self.steps_by_age.front().cloned()
+        }
+
+        /// Returns the newest step in the preceding fragment, which was the last one to be added.
+        pub fn newest(&self) -> Option<T> {
+            self.steps_by_age.back().cloned()
+        }
+
+        /// Checks if `value` is currently in the preceding fragment.
+        ///
+        /// # Examples
+        ///
+        /// ```
+        /// use turtles::tunnel_utils::SortedTunnel;
+        ///
+        /// let mut sorted_tunnel = SortedTunnel::new(vec![5, 4, 5]);
+        /// sorted_tunnel.shift_right(7);
+        ///
+        /// // one of the 5s is still there
+        /// assert!(sorted_tunnel.contains(&5));
+        /// sorted_tunnel.shift_right(9);
+        /// // 4 was shifted out
+        /// assert!(!sorted_tunnel.contains(&4));
+        /// ```
+        pub fn contains(&self, value: &T) -> bool {
+            self.tunnel_map.contains_key(value)
+        }
+
+        /// Returns the steps of the preceding fragment in ascending order, including duplicates.
+        ///
+        /// # Examples
+        ///
+        /// ```
+        /// use turtles::tunnel_utils::SortedTunnel;
+        ///
+        /// let mut sorted_tunnel = SortedTunnel::new(vec![5, 4, 7]);
+        /// sorted_tunnel.shift_right(9);
+        /// sorted_tunnel.shift_right(7);
+        ///
+        /// assert_eq!(sorted_tunnel.window(), vec![7, 7, 9]);
+        /// ```
+        pub fn window(&self) -> Vec<T> {
+            self.tunnel_map
+                .iter()
+                .flat_map(|(step, count)| core::iter::repeat_n(step.clone(), count))
+                .collect()
+        }
+
+        /// Returns the step at the `p`-th percentile (from 0 to 100) of the preceding fragment, counting every duplicate.
+        ///
+        /// Steps cannot be interpolated, so the position `p / 100 * (len - 1)` in the sorted steps
+        /// is rounded down, and a `p` landing between two steps gives the lower one.
+        /// Returns `None` if the fragment is empty or `p` is outside of 0 to 100.
+        ///
+        /// The sorted steps are walked once, without allocating.
+        ///
+        /// # Examples
+        ///
+        /// ```
+        /// use turtles::tunnel_utils::SortedTunnel;
+        ///
+        /// let sorted_tunnel = SortedTunnel::new(vec![7, 1, 4, 2]);
+        ///
+        /// // the median of an even number of steps is the lower of the middle ones
+        /// assert_eq!(sorted_tunnel.percentile(50.0), Some(2));
+        /// assert_eq!(sorted_tunnel.percentile(0.0), Some(1));
+        /// assert_eq!(sorted_tunnel.percentile(100.0), Some(7));
+        /// assert_eq!(sorted_tunnel.percentile(101.0), None);
+        ///
+        /// let sorted_tunnel = SortedTunnel::new(vec![5, 5, 5, 9]);
+        ///
+        /// assert_eq!(sorted_tunnel.percentile(50.0), Some(5));
+        /// assert_eq!(sorted_tunnel.percentile(90.0), Some(5));
+        /// ```
+        pub fn percentile(&self, p: f64) -> Option<T> {
+            if self.is_empty() || !(0.0..=100.0).contains(&p) {
+                return None;
+            }
+            // the position is never negative, so the cast rounds it down
+            let position = (p / 100.0 * (self.len() - 1) as f64) as usize;
+
+            let mut preceding = 0;
+            for (step, count) in self.tunnel_map.iter() {
+                preceding += count;
+                if position < preceding {
+                    return Some(step.clone());
+                }
+            }
+            None
+        }
+
+        /// Consumes the preceding fragment and returns its steps in ascending order, including duplicates,
+        /// like [`window`](SortedTunnel::window).
+        ///
+        /// # Examples
+        ///
+        /// ```
+        /// use turtles::tunnel_utils::SortedTunnel;
+        ///
+        /// let mut sorted_tunnel = SortedTunnel::new(vec![5, 4, 7]);
+        /// sorted_tunnel.shift_right(4);
+        /// let len = sorted_tunnel.len();
+        /// let steps = sorted_tunnel.into_sorted_vec();
+        ///
+        /// assert_eq!(steps, vec![4, 4, 7]);
+        /// assert_eq!(steps.len(), len);
+        /// ```
+        pub fn into_sorted_vec(self) -> Vec<T> {
+            self.tunnel_map.into_sorted_vec()
         }
 
         /// Checks if the tunnel won't collapse after the next step.
+        /// With [`allow_repeat`](SortedTunnel::allow_repeat), a step equal to a preceding element is safe as well.
+        ///
+        /// # Examples
+        ///
+        /// ```
+        /// use turtles::tunnel_utils::SortedTunnel;
+        ///
+        /// let sorted_tunnel = SortedTunnel::new(vec![5, 4, 7, 4]);
+        ///
+        /// assert!(sorted_tunnel.is_tunnel_safe(11));
+        /// // 4 appears twice, so it can be paired with itself
+        /// assert!(sorted_tunnel.is_tunnel_safe(8));
+        /// // but 5 and 7 appear only once
+        /// assert!(!sorted_tunnel.is_tunnel_safe(10));
+        /// assert!(!sorted_tunnel.is_tunnel_safe(14));
+        ///
+        ///
+        /// // a sum can be smaller than every element when they are negative
+        /// let sorted_tunnel = SortedTunnel::new(vec![-2, 5, -1]);
+        ///
+        /// assert!(sorted_tunnel.is_tunnel_safe(-3));
+        /// assert!(sorted_tunnel.is_tunnel_safe(3));
+        /// assert!(!sorted_tunnel.is_tunnel_safe(-4));
+        ///
+        ///
+        /// // an element is added to itself with the same `+` as other pairs, so saturating types agree
+        /// use std::num::Saturating;
+        ///
+        /// let sorted_tunnel = SortedTunnel::new(vec![Saturating(200u8), Saturating(201)]);
+        /// assert!(sorted_tunnel.is_tunnel_safe(Saturating(255)));
+        ///
+        /// let sorted_tunnel = SortedTunnel::new(vec![Saturating(200u8), Saturating(200)]);
+        /// assert!(sorted_tunnel.is_tunnel_safe(Saturating(255)));
+        ///
+        /// // but only if it appears twice
+        /// let sorted_tunnel = SortedTunnel::new(vec![Saturating(200u8), Saturating(10)]);
+        /// assert!(!sorted_tunnel.is_tunnel_safe(Saturating(255)));
+        /// ```
         pub fn is_tunnel_safe(&self, new_step: T) -> bool {
-            'outer: for (i, (candidate_a, ages_a)) in self.tunnel_map.iter().enumerate() {
-                if candidate_a >= &new_step {
-                    return false;
+            (self.allow_repeat && self.tunnel_map.contains_key(&new_step))
+                || self.safe_pair(new_step).is_some()
+        }
+
+        /// Finds 2 preceding elements which sum up to the next step, keeping the tunnel safe.
+        /// Returns `None` if the tunnel would collapse after the next step.
+        ///
+        /// The same element can be paired with itself only if it appears at least twice.
+        ///
+        /// # Examples
+        ///
+        /// ```
+        /// use turtles::tunnel_utils::SortedTunnel;
+        ///
+        /// let sorted_tunnel = SortedTunnel::new(vec![5, 4, 7, 4]);
+        ///
+        /// assert_eq!(sorted_tunnel.safe_pair(12), Some((5, 7)));
+        /// assert_eq!(sorted_tunnel.safe_pair(8), Some((4, 4)));
+        /// assert_eq!(sorted_tunnel.safe_pair(10), None);
+        /// ```
+        pub fn safe_pair(&self, new_step: T) -> Option<(T, T)> {
+            self.safe_pair_by(|candidate_a, candidate_b| {
+                (candidate_a.clone() + candidate_b.clone()).cmp(&new_step)
+            })
+        }
+
+        /// Counts the distinct pairs of preceding elements summing up to `target`,
+        /// of which [`safe_pair`](SortedTunnel::safe_pair) returns one.
+        /// Pairs are unordered and counted once no matter how many times their elements appear.
+        /// An element is paired with itself only if it appears twice,
+        /// unless [`allow_self_pair`](SortedTunnel::allow_self_pair) is set.
+        ///
+        /// It's 0 exactly when [`is_tunnel_safe`](SortedTunnel::is_tunnel_safe) returns `false`,
+        /// unless [`allow_repeat`](SortedTunnel::allow_repeat) makes a repeated step safe without any pair.
+        ///
+        /// # Examples
+        ///
+        /// ```
+        /// use turtles::tunnel_utils::SortedTunnel;
+        ///
+        /// let sorted_tunnel = SortedTunnel::new(vec![1, 2, 3, 4, 5, 5]);
+        ///
+        /// // 1 + 5 and 2 + 4
+        /// assert_eq!(sorted_tunnel.pair_count(6), 2);
+        /// // 1 + 3, but 2 appears only once
+        /// assert_eq!(sorted_tunnel.pair_count(4), 1);
+        /// assert_eq!(sorted_tunnel.pair_count(10), 1);
+        /// assert_eq!(sorted_tunnel.pair_count(11), 0);
+        ///
+        /// // a repeat is safe without any pair
+        /// let sorted_tunnel = SortedTunnel::new(vec![5, 4]).allow_repeat(true);
+        /// assert!(sorted_tunnel.is_tunnel_safe(5));
+        /// assert_eq!(sorted_tunnel.pair_count(5), 0);
+        /// assert!(!sorted_tunnel.is_tunnel_safe(11));
+        /// ```
+        pub fn pair_count(&self, target: T) -> usize {
+            let mut count = 0;
+            // every matching pair is rejected, so the search goes on until all of them are counted
+            self.find_pair_where(
+                self.allow_self_pair,
+                |candidate_a, candidate_b| (candidate_a.clone() + candidate_b.clone()).cmp(&target),
+                |_, _| {
+                    count += 1;
+                    false
+                },
+            );
+            count
+        }
+
+        /// Returns every step from `lo` to `hi` (inclusive) which wouldn't collapse the tunnel, in ascending order.
+        /// The preceding fragment is left untouched, so it can be used to see what could happen next.
+        ///
+        /// Each step in the range is checked separately,
+        /// so it takes O(r * n) comparisons for r steps in the range and n preceding elements.
+        ///
+        /// # Examples
+        ///
+        /// ```
+        /// use turtles::tunnel_utils::SortedTunnel;
+        ///
+        /// let sorted_tunnel = SortedTunnel::new(vec![5, 4, 7]);
+        ///
+        /// assert_eq!(sorted_tunnel.safe_steps_in_range(0, 20), vec![9, 11, 12]);
+        /// assert_eq!(sorted_tunnel.safe_steps_in_range(13, 20), vec![]);
+        /// ```
+        pub fn safe_steps_in_range(&self, lo: T, hi: T) -> Vec<T>
+        where
+            RangeInclusive<T>: Iterator<Item = T>,
+        {
+            (lo..=hi)
+                .filter(|step| self.is_tunnel_safe(step.clone()))
+                .collect()
+        }
+
+        /// Works like [`is_tunnel_safe`](SortedTunnel::is_tunnel_safe), but sums which would overflow `T` are skipped.
+        pub fn is_tunnel_safe_checked(&self, new_step: T) -> bool
+        where
+            T: CheckedAdd,
+        {
+            self.safe_pair_by(|candidate_a, candidate_b| {
+                candidate_a
+                    .checked_add(candidate_b)
+                    .map_or_else(|overflow| overflow, |sum| sum.cmp(&new_step))
+            })
+            .is_some()
+        }
+
+        /// Finds 2 preceding elements for which `compare_sum` (comparing their sum with the next step) returns `Equal`.
+        /// An element is compared with itself only if it appears at least twice,
+        /// unless [`allow_self_pair`](SortedTunnel::allow_self_pair) is set.
+        ///
+        /// This allows to customize how the sums are computed and compared, e.g. to tolerate rounding errors.
+        /// The comparison has to grow with both elements, like a regular sum does.
+        ///
+        /// The elements are walked from both ends of the preceding fragment at once,
+        /// so it takes O(n) comparisons for n preceding elements.
+        pub fn safe_pair_by(&self, compare_sum: impl Fn(&T, &T) -> Ordering) -> Option<(T, T)> {
+            self.find_pair_by(self.allow_self_pair, compare_sum)
+        }
+
+        /// Works like [`safe_pair_by`](SortedTunnel::safe_pair_by), but with `allow_self_pair`
+        /// every element can be compared with itself.
+        fn find_pair_by(
+            &self,
+            allow_self_pair: bool,
+            compare_sum: impl FnMut(&T, &T) -> Ordering,
+        ) -> Option<(T, T)> {
+            self.find_pair_where(allow_self_pair, compare_sum, |_, _| true)
+        }
+
+        /// Works like [`find_pair_by`](SortedTunnel::find_pair_by), but skips the pairs `accept` rejects.
+        fn find_pair_where(
+            &self,
+            allow_self_pair: bool,
+            mut compare_sum: impl FnMut(&T, &T) -> Ordering,
+            mut accept: impl FnMut(&T, &T) -> bool,
+        ) -> Option<(T, T)> {
+            let mut candidates = self.tunnel_map.iter();
+            let mut lower = candidates.next()?;
+            let mut upper = candidates.next_back().unwrap_or(lower);
+
+            loop {
+                let (candidate_a, count_a) = lower;
+                let (candidate_b, _) = upper;
+
+                // the cursors met, so the only pair left is the element with itself
+                if candidate_a == candidate_b {
+                    return ((allow_self_pair || count_a > 1)
+                        && compare_sum(candidate_a, candidate_a) == Ordering::Equal
+                        && accept(candidate_a, candidate_a))
+                    .then(|| (candidate_a.clone(), candidate_a.clone()));
                 }
 
-                if ages_a.len() > 1 && *candidate_a * 2 == new_step {
-                    return true;
+                match compare_sum(candidate_a, candidate_b) {
+                    Ordering::Equal if accept(candidate_a, candidate_b) => {
+                        return Some((candidate_a.clone(), candidate_b.clone()))
+                    }
+                    // any other matching pair lies between the two elements
+                    Ordering::Equal => {
+                        lower = candidates.next()?;
+                        upper = candidates.next_back().unwrap_or(lower);
+                    }
+                    Ordering::Less => lower = candidates.next().unwrap_or(upper),
+                    Ordering::Greater => upper = candidates.next_back().unwrap_or(lower),
+                }
+            }
+        }
+
+        /// Works like [`safe_pair`](SortedTunnel::safe_pair), but the elements of the pair
+        /// can differ by at most `max_spread`.
+        /// A spread which would overflow `T` exceeds any `max_spread`.
+        ///
+        /// # Examples
+        ///
+        /// ```
+        /// use turtles::tunnel_utils::SortedTunnel;
+        ///
+        /// let sorted_tunnel = SortedTunnel::new(vec![1, 5, 6, 10]);
+        ///
+        /// assert_eq!(sorted_tunnel.safe_pair(11), Some((1, 10)));
+        /// assert_eq!(sorted_tunnel.safe_pair_within(11, 1), Some((5, 6)));
+        /// assert_eq!(sorted_tunnel.safe_pair_within(15, 4), None);
+        ///
+        /// // 100 - (-100) doesn't fit in `i8`
+        /// let sorted_tunnel = SortedTunnel::new(vec![-100i8, 100]);
+        /// assert_eq!(sorted_tunnel.safe_pair_within(0, 5), None);
+        /// assert_eq!(sorted_tunnel.safe_pair_within(0, i8::MAX), None);
+        /// assert_eq!(sorted_tunnel.safe_pair(0), Some((-100, 100)));
+        /// ```
+        pub fn safe_pair_within(&self, new_step: T, max_spread: T) -> Option<(T, T)>
+        where
+            T: CheckedSub,
+        {
+            self.find_pair_where(
+                self.allow_self_pair,
+                |candidate_a, candidate_b| {
+                    (candidate_a.clone() + candidate_b.clone()).cmp(&new_step)
+                },
+                |candidate_a, candidate_b| {
+                    candidate_b
+                        .checked_sub(candidate_a)
+                        .is_ok_and(|spread| spread <= max_spread)
+                },
+            )
+        }
+
+        /// Finds 2 preceding elements for which the larger minus the smaller one is `new_step`,
+        /// and returns them with the smaller one first.
+        /// An element is paired with itself (giving 0) only if it appears at least twice,
+        /// unless [`allow_self_pair`](SortedTunnel::allow_self_pair) is set.
+        /// Differences which would overflow `T` are skipped, as they cannot be equal to `new_step`.
+        ///
+        /// Both elements are walked from the smallest one, the larger one ahead of the smaller one,
+        /// so it takes O(n) comparisons for n preceding elements.
+        ///
+        /// # Examples
+        ///
+        /// ```
+        /// use turtles::tunnel_utils::SortedTunnel;
+        ///
+        /// let sorted_tunnel = SortedTunnel::new(vec![10, 3, 7, 3]);
+        ///
+        /// assert_eq!(sorted_tunnel.safe_difference(4), Some((3, 7)));
+        /// assert_eq!(sorted_tunnel.safe_difference(7), Some((3, 10)));
+        /// assert_eq!(sorted_tunnel.safe_difference(0), Some((3, 3)));
+        /// assert_eq!(sorted_tunnel.safe_difference(5), None);
+        /// assert_eq!(sorted_tunnel.safe_difference(-4), None);
+        ///
+        /// // 100 - (-100) doesn't fit in `i8`
+        /// let sorted_tunnel = SortedTunnel::new(vec![-100i8, 100, 105]);
+        /// assert_eq!(sorted_tunnel.safe_difference(5), Some((100, 105)));
+        /// assert_eq!(SortedTunnel::new(vec![-100i8, 100]).safe_difference(5), None);
+        /// ```
+        pub fn safe_difference(&self, new_step: T) -> Option<(T, T)>
+        where
+            T: CheckedSub,
+        {
+            let mut lower_candidates = self.tunnel_map.iter();
+            let mut upper_candidates = self.tunnel_map.iter();
+            let mut lower = lower_candidates.next()?;
+            let mut upper = upper_candidates.next()?;
+
+            loop {
+                let (candidate_a, count_a) = lower;
+                let (candidate_b, _) = upper;
+
+                let same_element = candidate_a == candidate_b;
+                // an element appearing once cannot be subtracted from itself, so a larger one is needed
+                if same_element && !(self.allow_self_pair || count_a > 1) {
+                    upper = upper_candidates.next()?;
+                    continue;
+                }
+
+                let difference = candidate_b.checked_sub(candidate_a);
+                match difference
+                    .map_or_else(|overflow| overflow, |difference| difference.cmp(&new_step))
+                {
+                    Ordering::Equal => return Some((candidate_a.clone(), candidate_b.clone())),
+                    Ordering::Less => upper = upper_candidates.next()?,
+                    // 0 is already the smallest difference
+                    Ordering::Greater if same_element => return None,
+                    Ordering::Greater => lower = lower_candidates.next()?,
+                }
+            }
+        }
+
+        /// Returns the sum of 2 preceding elements closest to `target`, which is `target` itself if it's such a sum.
+        /// If the closest sums below and above `target` are equally far from it, the one below is returned.
+        /// Returns `None` if there's no pair of elements at all.
+        ///
+        /// # Examples
+        ///
+        /// ```
+        /// use turtles::tunnel_utils::SortedTunnel;
+        ///
+        /// let sorted_tunnel = SortedTunnel::new(vec![4, 7, 9]);
+        ///
+        /// assert_eq!(sorted_tunnel.nearest_safe(16), Some(16));
+        /// assert_eq!(sorted_tunnel.nearest_safe(14), Some(13));
+        /// // 11 and 13 are equally close
+        /// assert_eq!(sorted_tunnel.nearest_safe(12), Some(11));
+        /// assert_eq!(sorted_tunnel.nearest_safe(15), Some(16));
+        /// assert_eq!(sorted_tunnel.nearest_safe(100), Some(16));
+        /// assert_eq!(SortedTunnel::new(vec![4]).nearest_safe(8), None);
+        ///
+        /// // a repeat is safe, but it isn't a sum
+        /// let sorted_tunnel = SortedTunnel::new(vec![5, 4]).allow_repeat(true);
+        /// assert_eq!(sorted_tunnel.nearest_safe(5), Some(9));
+        /// ```
+        pub fn nearest_safe(&self, target: T) -> Option<T>
+        where
+            T: Sub<Output = T>,
+        {
+            // a repeat allowed by `allow_repeat` isn't a sum
+            if self.safe_pair(target.clone()).is_some() {
+                return Some(target);
+            }
+            match self.nearest_sums(target.clone()) {
+                (Some(below), Some(above)) => {
+                    if above.clone() - target.clone() < target - below.clone() {
+                        Some(above)
+                    } else {
+                        Some(below)
+                    }
+                }
+                (below, above) => below.or(above),
+            }
+        }
+
+        /// Returns the smallest and the greatest sum of 2 preceding elements, or `None` if there's no pair.
+        /// Elements are added to themselves like in [`safe_pair`](SortedTunnel::safe_pair).
+        ///
+        /// Only the smallest and the greatest elements are read, so it takes O(log d) for d distinct elements.
+        ///
+        /// # Panics
+        ///
+        /// The sums are computed with `+`, so this panics if `+` panics when the smallest or the greatest sum
+        /// overflows `T`, as it does for integers in debug builds, e.g. for `u8` elements 200 and 100.
+        ///
+        /// # Examples
+        ///
+        /// ```
+        /// use turtles::tunnel_utils::SortedTunnel;
+        ///
+        /// assert_eq!(SortedTunnel::new(vec![5, 4, 7, 9]).sum_bounds(), Some((9, 16)));
+        /// assert_eq!(SortedTunnel::new(vec![4, 4, 7]).sum_bounds(), Some((8, 11)));
+        /// assert_eq!(SortedTunnel::new(vec![4]).sum_bounds(), None);
+        /// assert_eq!(SortedTunnel::new(vec![4]).allow_self_pair(true).sum_bounds(), Some((8, 8)));
+        /// ```
+        pub fn sum_bounds(&self) -> Option<(T, T)> {
+            let can_double = |count| self.allow_self_pair || count > 1;
+
+            let mut ascending = self.tunnel_map.iter();
+            let (smallest, smallest_count) = ascending.next()?;
+            let smallest_partner = if can_double(smallest_count) {
+                smallest
+            } else {
+                ascending.next()?.0
+            };
+
+            let mut descending = self.tunnel_map.iter();
+            let (greatest, greatest_count) = descending.next_back()?;
+            let greatest_partner = if can_double(greatest_count) {
+                greatest
+            } else {
+                descending.next_back()?.0
+            };
+
+            Some((
+                smallest.clone() + smallest_partner.clone(),
+                greatest.clone() + greatest_partner.clone(),
+            ))
+        }
+
+        /// Returns up to `k` distinct sums of 2 preceding elements closest to `target`, from the closest one,
+        /// like [`nearest_safe`](SortedTunnel::nearest_safe) returns the first of them.
+        /// Sums equally far from `target` are ordered from the lower one, and sums which would overflow `T` are skipped.
+        /// A sum too far from `target` for the distance to fit in `T` is still ordered correctly.
+        ///
+        /// Every pair of distinct elements is summed up, so it takes O(d^2 * log d) for d distinct elements.
+        ///
+        /// # Examples
+        ///
+        /// ```
+        /// use turtles::tunnel_utils::SortedTunnel;
+        ///
+        /// // sums are 5, 7, 10, 11, 14 and 16
+        /// let sorted_tunnel = SortedTunnel::new(vec![1, 4, 6, 10]);
+        ///
+        /// // 7 and 11 are equally close
+        /// assert_eq!(sorted_tunnel.nearest_safe_k(9, 3), vec![10, 7, 11]);
+        /// assert_eq!(sorted_tunnel.nearest_safe_k(10, 2), vec![10, 11]);
+        /// assert_eq!(sorted_tunnel.nearest_safe_k(0, 10), vec![5, 7, 10, 11, 14, 16]);
+        /// assert_eq!(SortedTunnel::new(vec![4]).nearest_safe_k(8, 3), vec![]);
+        ///
+        /// // 200 + 100 doesn't fit in `u8`
+        /// assert_eq!(SortedTunnel::new(vec![200u8, 100, 1]).nearest_safe_k(5, 3), vec![101, 201]);
+        ///
+        /// // 100 - (-120) doesn't fit in `i8`
+        /// let sorted_tunnel = SortedTunnel::new(vec![-100i8, -20, 50]);
+        /// assert_eq!(sorted_tunnel.nearest_safe_k(100, 3), vec![30, -50, -120]);
+        /// assert_eq!(sorted_tunnel.nearest_safe_k(-128, 3), vec![-120, -50, 30]);
+        /// ```
+        pub fn nearest_safe_k(&self, target: T, k: usize) -> Vec<T>
+        where
+            T: CheckedAdd + CheckedSub,
+        {
+            // sums are in ascending order and the sort is stable, so the lower one of equally close sums stays first
+            let mut sums: Vec<T> = self.pair_sums().into_iter().collect();
+            sums.sort_by(|sum_a, sum_b| Self::cmp_distance(sum_a, sum_b, &target));
+            sums.truncate(k);
+            sums
+        }
+
+        /// Collects the distinct sums of 2 preceding elements, pairing elements with themselves
+        /// like [`safe_pair`](SortedTunnel::safe_pair) and skipping sums which would overflow `T`.
+        fn pair_sums(&self) -> BTreeSet<T>
+        where
+            T: CheckedAdd,
+        {
+            let candidates: Vec<(&T, usize)> = self.tunnel_map.iter().collect();
+            let mut sums = BTreeSet::new();
+            for (i, &(candidate_a, count_a)) in candidates.iter().enumerate() {
+                if self.allow_self_pair || count_a > 1 {
+                    sums.extend(candidate_a.checked_add(candidate_a).ok());
+                }
+                for &(candidate_b, _) in &candidates[i + 1..] {
+                    sums.extend(candidate_a.checked_add(candidate_b).ok());
+                }
+            }
+            sums
+        }
+
+        /// Compares how far `sum_a` and `sum_b` are from `target`.
+        ///
+        /// A distance which doesn't fit in `T` is greater than any which does. The range of `T` is too narrow
+        /// for such distances on both sides of `target`, so they're ordered by the sums themselves.
+        fn cmp_distance(sum_a: &T, sum_b: &T, target: &T) -> Ordering
+        where
+            T: CheckedSub,
+        {
+            // the greater value comes first, so the distance never goes below 0 for unsigned types
+            let distance = |sum: &T| {
+                if sum < target {
+                    target.checked_sub(sum)
+                } else {
+                    sum.checked_sub(target)
+                }
+            };
+            match (distance(sum_a), distance(sum_b)) {
+                (Ok(distance_a), Ok(distance_b)) => distance_a.cmp(&distance_b),
+                (Ok(_), Err(_)) => Ordering::Less,
+                (Err(_), Ok(_)) => Ordering::Greater,
+                (Err(_), Err(_)) if sum_a < target => sum_b.cmp(sum_a),
+                (Err(_), Err(_)) => sum_a.cmp(sum_b),
+            }
+        }
+
+        /// Returns the smallest step which would collapse the tunnel among the values from the smallest
+        /// to the greatest sum of 2 preceding elements (see [`sum_bounds`](SortedTunnel::sum_bounds)),
+        /// so the first gap between the sums.
+        ///
+        /// Every value below the smallest sum or above the greatest one collapses the tunnel anyway,
+        /// so `None` is returned if each value in that range is a sum, or if there's no pair of elements at all.
+        /// Sums which would overflow `T` are skipped, so the range ends at the greatest one which fits.
+        /// Steps have to be integers, stepping up by 1, which `T::from(true)` is.
+        ///
+        /// Every pair of distinct elements is summed up, so it takes O(d^2 * log d) for d distinct elements.
+        ///
+        /// # Examples
+        ///
+        /// ```
+        /// use turtles::tunnel_utils::SortedTunnel;
+        ///
+        /// // sums are 5, 7, 10, 11, 14 and 16
+        /// let sorted_tunnel = SortedTunnel::new(vec![1, 4, 6, 10]);
+        /// assert_eq!(sorted_tunnel.smallest_unsafe(), Some(6));
+        ///
+        /// // sums are 3, 4 and 5
+        /// assert_eq!(SortedTunnel::new(vec![1, 2, 3]).smallest_unsafe(), None);
+        /// assert_eq!(SortedTunnel::new(vec![3, 1, 2, 6]).smallest_unsafe(), Some(6));
+        ///
+        /// assert_eq!(SortedTunnel::new(vec![-3, 0, 5]).smallest_unsafe(), Some(-2));
+        /// assert_eq!(SortedTunnel::new(vec![4]).smallest_unsafe(), None);
+        /// assert_eq!(SortedTunnel::new(vec![u8::MAX - 1, 0, 1]).smallest_unsafe(), Some(2));
+        ///
+        /// // 200 + 100 doesn't fit in `u8`, so 100 + 100 is the only sum
+        /// assert_eq!(SortedTunnel::new(vec![200u8, 100, 100]).smallest_unsafe(), None);
+        /// assert_eq!(SortedTunnel::new(vec![200u8, 100]).smallest_unsafe(), None);
+        /// ```
+        pub fn smallest_unsafe(&self) -> Option<T>
+        where
+            T: CheckedAdd + From<bool>,
+        {
+            let one = T::from(true);
+            let mut sums = self.pair_sums().into_iter();
+            let mut expected = sums.next()?.checked_add(&one).ok()?;
+            for sum in sums {
+                if sum != expected {
+                    return Some(expected);
+                }
+                // the greatest representable sum leaves no gap above it
+                expected = sum.checked_add(&one).ok()?;
+            }
+            None
+        }
+
+        /// Returns the pairs of preceding elements which are the candidates to adjust
+        /// so that `target` (usually a step at which the tunnel collapses) becomes safe.
+        ///
+        /// A candidate is a pair whose sum is the nearest to `target`, so it's off by the smallest delta,
+        /// and every pair off by that delta is returned, e.g. one with its sum below `target` and one above it.
+        /// Pairs are returned once each as `(smaller, greater)`, ordered by the smaller element.
+        /// If `target` is already a sum of 2 elements, these are exactly the pairs summing up to it.
+        /// Elements are paired with themselves like in [`safe_pair`](SortedTunnel::safe_pair),
+        /// and there are no candidates without any pair.
+        /// Pairs whose sum would overflow `T` are skipped, like in [`nearest_safe_k`](SortedTunnel::nearest_safe_k),
+        /// while a sum too far from `target` for the delta to fit in `T` can still be the nearest one.
+        ///
+        /// Every pair of distinct elements is summed up, so it takes O(d^2) for d distinct elements.
+        ///
+        /// # Examples
+        ///
+        /// ```
+        /// use turtles::tunnel_utils::SortedTunnel;
+        ///
+        /// // sums are 5, 7, 10, 11, 14 and 16
+        /// let sorted_tunnel = SortedTunnel::new(vec![1, 4, 6, 10]);
+        ///
+        /// // 5 and 7 are both 1 away from 6
+        /// assert_eq!(sorted_tunnel.unblocking_candidates(6), vec![(1, 4), (1, 6)]);
+        /// assert_eq!(sorted_tunnel.unblocking_candidates(9), vec![(4, 6)]);
+        /// assert_eq!(sorted_tunnel.unblocking_candidates(15), vec![(4, 10), (6, 10)]);
+        /// assert_eq!(sorted_tunnel.unblocking_candidates(10), vec![(4, 6)]);
+        ///
+        /// // 4 appears once, so it isn't paired with itself
+        /// assert_eq!(SortedTunnel::new(vec![4]).unblocking_candidates(8), vec![]);
+        /// assert_eq!(SortedTunnel::new(vec![4, 4]).unblocking_candidates(9), vec![(4, 4)]);
+        ///
+        /// // 200 + 100 doesn't fit in `u8`
+        /// assert_eq!(SortedTunnel::new(vec![200u8, 100]).unblocking_candidates(5), vec![]);
+        /// assert_eq!(SortedTunnel::new(vec![200u8, 100, 1]).unblocking_candidates(5), vec![(1, 100)]);
+        ///
+        /// // 100 - (-120) doesn't fit in `i8`
+        /// assert_eq!(SortedTunnel::new(vec![-100i8, -20]).unblocking_candidates(100), vec![(-100, -20)]);
+        /// assert_eq!(SortedTunnel::new(vec![-100i8, -20, 50]).unblocking_candidates(100), vec![(-20, 50)]);
+        /// ```
+        pub fn unblocking_candidates(&self, target: T) -> Vec<(T, T)>
+        where
+            T: CheckedAdd + CheckedSub,
+        {
+            let elements: Vec<(&T, usize)> = self.tunnel_map.iter().collect();
+            let mut nearest: Option<T> = None;
+            let mut candidates = Vec::new();
+            for (i, &(candidate_a, count_a)) in elements.iter().enumerate() {
+                let partners = if self.allow_self_pair || count_a > 1 {
+                    &elements[i..]
+                } else {
+                    &elements[i + 1..]
+                };
+                for &(candidate_b, _) in partners {
+                    let Ok(sum) = candidate_a.checked_add(candidate_b) else {
+                        continue;
+                    };
+                    match nearest
+                        .as_ref()
+                        .map(|nearest| Self::cmp_distance(&sum, nearest, &target))
+                    {
+                        Some(Ordering::Greater) => continue,
+                        Some(Ordering::Equal) => {}
+                        Some(Ordering::Less) | None => {
+                            nearest = Some(sum);
+                            candidates.clear();
+                        }
+                    }
+                    candidates.push((candidate_a.clone(), candidate_b.clone()));
+                }
+            }
+            candidates
+        }
+
+        /// Finds the greatest sum of 2 preceding elements smaller than `target`
+        /// and the smallest one greater than `target`.
+        /// Elements are added to themselves like in [`safe_pair`](SortedTunnel::safe_pair).
+        pub(crate) fn nearest_sums(&self, target: T) -> (Option<T>, Option<T>) {
+            let mut nearest_below: Option<T> = None;
+            let mut nearest_above: Option<T> = None;
+            self.find_pair_by(self.allow_self_pair, |candidate_a, candidate_b| {
+                let sum = candidate_a.clone() + candidate_b.clone();
+                let ordering = sum.cmp(&target);
+                match ordering {
+                    Ordering::Less => {
+                        nearest_below = Some(match nearest_below.take() {
+                            Some(below) => below.max(sum),
+                            None => sum,
+                        })
+                    }
+                    Ordering::Greater => {
+                        nearest_above = Some(match nearest_above.take() {
+                            Some(above) => above.min(sum),
+                            None => sum,
+                        })
+                    }
+                    Ordering::Equal => {}
+                }
+                ordering
+            });
+            (nearest_below, nearest_above)
+        }
+
+        /// Checks if the tunnel won't collapse after the next step,
+        /// when the step has to be a sum of exactly `k` preceding elements.
+        ///
+        /// For `k == 2` this takes O(n) for n preceding elements, like [`is_tunnel_safe`](SortedTunnel::is_tunnel_safe),
+        /// and for `k > 2` it takes O(n^`k`) in the worst case.
+        /// `k == 0` never keeps the tunnel safe, unless the step is a repeat.
+        ///
+        /// [`allow_self_pair`](SortedTunnel::allow_self_pair) and [`allow_repeat`](SortedTunnel::allow_repeat)
+        /// are respected, so for `k == 2` it agrees with [`is_tunnel_safe`](SortedTunnel::is_tunnel_safe),
+        /// and a repeat allowed by the latter keeps the tunnel safe for any `k`.
+        ///
+        /// # Examples
+        ///
+        /// ```
+        /// use turtles::tunnel_utils::SortedTunnel;
+        ///
+        /// let sorted_tunnel = SortedTunnel::new(vec![5, 4, 7]);
+        /// assert!(sorted_tunnel.is_tunnel_safe_k(16, 3));
+        /// assert!(!sorted_tunnel.is_tunnel_safe_k(5, 2));
+        ///
+        /// let sorted_tunnel = sorted_tunnel.allow_repeat(true).allow_self_pair(true);
+        /// assert_eq!(sorted_tunnel.is_tunnel_safe_k(5, 2), sorted_tunnel.is_tunnel_safe(5));
+        /// assert_eq!(sorted_tunnel.is_tunnel_safe_k(10, 2), sorted_tunnel.is_tunnel_safe(10));
+        /// assert!(sorted_tunnel.is_tunnel_safe_k(5, 3));
+        /// ```
+        pub fn is_tunnel_safe_k(&self, new_step: T, k: usize) -> bool {
+            (self.allow_repeat && self.tunnel_map.contains_key(&new_step))
+                || self.is_sum_of(new_step, k, self.allow_self_pair)
+        }
+
+        /// Checks if `new_step` is a sum of exactly `k` preceding elements.
+        /// With `allow_self_pair`, every element can be used any number of times.
+        /// A repeat isn't safe on its own here, whatever [`allow_repeat`](SortedTunnel::allow_repeat) is.
+        pub(crate) fn is_sum_of(&self, new_step: T, k: usize, allow_self_pair: bool) -> bool {
+            match k {
+                0 => false,
+                1 => self.tunnel_map.contains_key(&new_step),
+                2 => self
+                    .find_pair_by(allow_self_pair, |candidate_a, candidate_b| {
+                        (candidate_a.clone() + candidate_b.clone()).cmp(&new_step)
+                    })
+                    .is_some(),
+                _ => {
+                    let mut candidates: Vec<(T, usize)> = self
+                        .tunnel_map
+                        .iter()
+                        .map(|(step, count)| {
+                            // no sum can use an element more than k times
+                            let available = if allow_self_pair { k } else { count };
+                            (step.clone(), available)
+                        })
+                        .collect();
+                    Self::is_sum_of_k(&mut candidates, 0, k, None, &new_step)
+                }
+            }
+        }
+
+        /// Searches for `k` more elements from `candidates[start..]` which, added to `partial_sum`, give `target`.
+        /// `candidates` holds distinct steps in ascending order and how many of them are still available.
+        fn is_sum_of_k(
+            candidates: &mut [(T, usize)],
+            start: usize,
+            k: usize,
+            partial_sum: Option<&T>,
+            target: &T,
+        ) -> bool {
+            for i in start..candidates.len() {
+                let (candidate, available) = &candidates[i];
+                if *available == 0 {
+                    continue;
                 }
+                let sum = match partial_sum {
+                    Some(partial_sum) => partial_sum.clone() + candidate.clone(),
+                    None => candidate.clone(),
+                };
 
-                for (candidate_b, _) in self.tunnel_map.iter().skip(i + 1) {
-                    match (*candidate_a + *candidate_b).cmp(&new_step) {
+                if k == 1 {
+                    // candidates are sorted, so the sums only grow from here
+                    match sum.cmp(target) {
                         Ordering::Equal => return true,
-                        Ordering::Greater => continue 'outer,
-                        _ => continue,
+                        Ordering::Greater => return false,
+                        Ordering::Less => continue,
                     }
                 }
+
+                candidates[i].1 -= 1;
+                // the same candidate can be reused while it's still available
+                let found = Self::is_sum_of_k(candidates, i, k - 1, Some(&sum), target);
+                candidates[i].1 += 1;
+                if found {
+                    return true;
+                }
             }
             false
         }
     }
+
+    impl SortedTunnel<u128> {
+        /// Version of the format written by [`to_bytes`](SortedTunnel::to_bytes).
+        pub const FORMAT_VERSION: u8 = 2;
+
+        const ALLOW_SELF_PAIR_FLAG: u8 = 1;
+        const ALLOW_REPEAT_FLAG: u8 = 2;
+
+        /// Encodes the preceding fragment, so it can be restored with [`from_bytes`](SortedTunnel::from_bytes),
+        /// e.g. to checkpoint a long analysis.
+        ///
+        /// The format starts with the version byte, [`FORMAT_VERSION`](SortedTunnel::FORMAT_VERSION),
+        /// which changes whenever the layout does. All the numbers are little-endian:
+        ///
+        /// - 1 byte of the version,
+        /// - 1 byte of flags: 1 for [`allow_self_pair`](SortedTunnel::allow_self_pair),
+        ///   2 for [`allow_repeat`](SortedTunnel::allow_repeat),
+        /// - 8 bytes (`u64`) of steps an under-filled window still takes before it slides,
+        ///   see [`with_capacity`](SortedTunnel::with_capacity),
+        /// - 8 bytes (`u64`) of steps appended into that room, which [`shift_left`](SortedTunnel::shift_left) takes back,
+        /// - 8 bytes (`u64`) of the insertion counter of the oldest step, telling if any step was removed yet,
+        /// - 8 bytes (`u64`) of the number of steps,
+        /// - for every step in sorted order (equal steps from the oldest), 16 bytes of its value (`u128`)
+        ///   and 8 bytes of its age (`u64`).
+        ///
+        /// The backend isn't stored, the fragment is restored in [`Backend::default`].
+        ///
+        /// # Examples
+        ///
+        /// ```
+        /// use turtles::tunnel_utils::SortedTunnel;
+        ///
+        /// let mut sorted_tunnel = SortedTunnel::new(vec![5u128, 4, 7]).allow_self_pair(true);
+        /// sorted_tunnel.shift_right(4);
+        ///
+        /// let bytes = sorted_tunnel.to_bytes();
+        /// assert_eq!(bytes[0], SortedTunnel::FORMAT_VERSION);
+        /// assert_eq!(bytes.len(), 34 + 3 * 24);
+        ///
+        /// let mut restored = SortedTunnel::from_bytes(&bytes).unwrap();
+        /// assert_eq!(format!("{restored:?}"), format!("{sorted_tunnel:?}"));
+        /// assert!(restored.is_tunnel_safe(8));
+        ///
+        /// // the oldest step is still the first one to go
+        /// restored.shift_right(11);
+        /// sorted_tunnel.shift_right(11);
+        /// assert_eq!(restored.oldest(), Some(7));
+        /// assert_eq!(restored.to_bytes(), sorted_tunnel.to_bytes());
+        ///
+        ///
+        /// // shifts are undone the same way after restoring, even those which filled an under-filled window
+        /// let mut sorted_tunnel = SortedTunnel::with_capacity(3, vec![5u128]);
+        /// sorted_tunnel.shift_right(4);
+        /// let mut restored = SortedTunnel::from_bytes(&sorted_tunnel.to_bytes()).unwrap();
+        ///
+        /// for step in [9, 13, 22] {
+        ///     sorted_tunnel.shift_right(step);
+        ///     restored.shift_right(step);
+        /// }
+        /// for step in [4, 5, 99, 99] {
+        ///     sorted_tunnel.shift_left(step);
+        ///     restored.shift_left(step);
+        ///     assert_eq!(format!("{restored:?}"), format!("{sorted_tunnel:?}"));
+        /// }
+        /// assert_eq!(restored.window(), vec![5]);
+        ///
+        /// // the room left is filled again before any step is removed
+        /// sorted_tunnel.shift_right_many([7, 8, 10]);
+        /// restored.shift_right_many([7, 8, 10]);
+        /// assert_eq!(restored.window(), vec![7, 8, 10]);
+        /// assert_eq!(restored.to_bytes(), sorted_tunnel.to_bytes());
+        /// ```
+        pub fn to_bytes(&self) -> Vec<u8> {
+            let mut by_value: Vec<(u128, usize)> =
+                self.steps_by_age.iter().copied().zip(0..).collect();
+            by_value.sort_unstable();
+
+            let mut bytes = Vec::with_capacity(34 + by_value.len() * 24);
+            bytes.push(Self::FORMAT_VERSION);
+            let mut flags = 0;
+            if self.allow_self_pair {
+                flags |= Self::ALLOW_SELF_PAIR_FLAG;
+            }
+            if self.allow_repeat {
+                flags |= Self::ALLOW_REPEAT_FLAG;
+            }
+            bytes.push(flags);
+            bytes.extend_from_slice(&(self.missing_steps as u64).to_le_bytes());
+            bytes.extend_from_slice(&(self.appended_steps as u64).to_le_bytes());
+            bytes.extend_from_slice(&(self.oldest_counter as u64).to_le_bytes());
+            bytes.extend_from_slice(&(by_value.len() as u64).to_le_bytes());
+            for (step, age) in by_value {
+                bytes.extend_from_slice(&step.to_le_bytes());
+                bytes.extend_from_slice(&(age as u64).to_le_bytes());
+            }
+            bytes
+        }
+
+        /// Restores a preceding fragment encoded with [`to_bytes`](SortedTunnel::to_bytes),
+        /// including the ages of its steps and the appends [`shift_left`](SortedTunnel::shift_left) can take back.
+        ///
+        /// # Errors
+        ///
+        /// Returns a [`DecodeError`] if the version isn't [`FORMAT_VERSION`](SortedTunnel::FORMAT_VERSION),
+        /// the bytes are cut short or too long, the ages aren't exactly `0..n` for `n` steps,
+        /// or the insertion counter doesn't fit in `usize`.
+        ///
+        /// # Examples
+        ///
+        /// ```
+        /// use turtles::tunnel_utils::SortedTunnel;
+        /// use turtles::DecodeError;
+        ///
+        /// let bytes = SortedTunnel::with_capacity(3, vec![5u128]).to_bytes();
+        /// let mut restored = SortedTunnel::from_bytes(&bytes).unwrap();
+        /// restored.shift_right_many([4, 9]);
+        /// assert_eq!(restored.window(), vec![4, 5, 9]);
+        ///
+        /// assert_eq!(SortedTunnel::from_bytes(&bytes[..40]).unwrap_err(), DecodeError::UnexpectedEnd);
+        ///
+        /// // fragments written in the first format, without the undo state, aren't read wrong
+        /// assert_eq!(SortedTunnel::from_bytes(&[1]).unwrap_err(), DecodeError::UnsupportedVersion(1));
+        /// ```
+        pub fn from_bytes(bytes: &[u8]) -> Result<Self, DecodeError> {
+            fn take<const N: usize>(bytes: &mut &[u8]) -> Result<[u8; N], DecodeError> {
+                let (taken, rest) = bytes
+                    .split_first_chunk::<N>()
+                    .ok_or(DecodeError::UnexpectedEnd)?;
+                *bytes = rest;
+                Ok(*taken)
+            }
+
+            let mut bytes = bytes;
+            let [version] = take(&mut bytes)?;
+            if version != Self::FORMAT_VERSION {
+                return Err(DecodeError::UnsupportedVersion(version));
+            }
+            let [flags] = take(&mut bytes)?;
+            if flags & !(Self::ALLOW_SELF_PAIR_FLAG | Self::ALLOW_REPEAT_FLAG) != 0 {
+                return Err(DecodeError::UnknownFlags(flags));
+            }
+            let missing_steps = u64::from_le_bytes(take(&mut bytes)?);
+            let appended_steps = u64::from_le_bytes(take(&mut bytes)?);
+            let oldest_counter = u64::from_le_bytes(take(&mut bytes)?);
+            // the counter wraps around at `usize`, so a greater one was written on a wider target
+            let oldest_counter = usize::try_from(oldest_counter)
+                .map_err(|_| DecodeError::CounterOverflow(oldest_counter))?;
+            let len = u64::from_le_bytes(take(&mut bytes)?);
+            // every step takes 24 bytes, so a length the bytes can't hold is rejected before allocating
+            if len > (bytes.len() / 24) as u64 {
+                return Err(DecodeError::UnexpectedEnd);
+            }
+
+            let mut steps_by_age = Vec::new();
+            steps_by_age.resize(len as usize, None);
+            for _ in 0..len {
+                let step = u128::from_le_bytes(take(&mut bytes)?);
+                let age = u64::from_le_bytes(take(&mut bytes)?);
+                let slot = usize::try_from(age)
+                    .ok()
+                    .and_then(|age| steps_by_age.get_mut(age))
+                    .filter(|slot| slot.is_none())
+                    .ok_or(DecodeError::InvalidAge(age))?;
+                *slot = Some(step);
+            }
+            if !bytes.is_empty() {
+                return Err(DecodeError::TrailingBytes);
+            }
+
+            let mut sorted_tunnel = Self::new(Vec::new());
+            sorted_tunnel.oldest_counter = oldest_counter;
+            sorted_tunnel.tunnel_length = steps_by_age.len().saturating_sub(1);
+            for (age, step) in steps_by_age.into_iter().flatten().enumerate() {
+                sorted_tunnel.add_duplicate(step, oldest_counter.wrapping_add(age));
+            }
+            sorted_tunnel.allow_self_pair = flags & Self::ALLOW_SELF_PAIR_FLAG != 0;
+            sorted_tunnel.allow_repeat = flags & Self::ALLOW_REPEAT_FLAG != 0;
+            // a window this long can't be filled on this target anyway
+            sorted_tunnel.missing_steps = usize::try_from(missing_steps).unwrap_or(usize::MAX);
+            sorted_tunnel.appended_steps = usize::try_from(appended_steps).unwrap_or(usize::MAX);
+            Ok(sorted_tunnel)
+        }
+    }
+
+    /// Lists the steps from the oldest to the newest one, together with their ages.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use turtles::tunnel_utils::SortedTunnel;
+    ///
+    /// let mut sorted_tunnel = SortedTunnel::new(vec![5, 4, 7]);
+    /// sorted_tunnel.shift_right(4);
+    ///
+    /// assert_eq!(format!("{sorted_tunnel:?}"), "[(4, age 0), (7, age 1), (4, age 2)]");
+    /// ```
+    impl<T: Ord + Add<Output = T> + Clone + Debug> Debug for SortedTunnel<T> {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            let mut steps = f.debug_list();
+            for (age, step) in self.steps_by_age.iter().enumerate() {
+                steps.entry(&format_args!("({step:?}, age {age})"));
+            }
+            steps.finish()
+        }
+    }
+
+    /// Collects the preceding fragment from the oldest to the newest step, just like [`SortedTunnel::new`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use turtles::tunnel_utils::SortedTunnel;
+    ///
+    /// let sorted_tunnel: SortedTunnel<u128> = vec![5, 4, 7].into_iter().collect();
+    ///
+    /// assert_eq!(sorted_tunnel.window(), SortedTunnel::new(vec![5, 4, 7]).window());
+    /// ```
+    impl<T: Ord + Add<Output = T> + Clone + Debug> FromIterator<T> for SortedTunnel<T> {
+        fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+            SortedTunnel::new(iter.into_iter().collect())
+        }
+    }
+
+    /// Appends steps as newer than every step already in the preceding fragment.
+    ///
+    /// Unlike [`SortedTunnel::shift_right`], no step is removed, so the fragment grows
+    /// and every following shift keeps the new length.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use turtles::tunnel_utils::SortedTunnel;
+    ///
+    /// let mut sorted_tunnel = SortedTunnel::new(vec![5, 4]);
+    /// sorted_tunnel.extend(vec![7]);
+    ///
+    /// assert_eq!(sorted_tunnel.len(), 3);
+    /// assert!(sorted_tunnel.is_tunnel_safe(9));
+    ///
+    /// // 5 was the oldest step, so it's removed first
+    /// sorted_tunnel.shift_right(9);
+    /// assert_eq!(sorted_tunnel.window(), vec![4, 7, 9]);
+    /// ```
+    impl<T: Ord + Add<Output = T> + Clone + Debug> Extend<T> for SortedTunnel<T> {
+        fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+            for step in iter {
+                self.push(step);
+            }
+        }
+    }
+}
+
+/// Reproducible generators of sample steps, e.g. for property tests and demos.
+///
+/// Available with the `test-util` feature. The same arguments always give the same steps,
+/// on every platform and with every version of the crate with the same major version.
+#[cfg(feature = "test-util")]
+pub mod gen {
+    use alloc::vec::Vec;
+
+    /// Largest step of the preceding fragment at the start of a generated stream.
+    const MAX_INITIAL_STEP: u64 = 100;
+
+    /// SplitMix64, small and good enough for test data, and without a dependency.
+    struct SplitMix64(u64);
+
+    impl SplitMix64 {
+        fn next(&mut self) -> u64 {
+            self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+            let mut z = self.0;
+            z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+            z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+            z ^ (z >> 31)
+        }
+
+        /// Returns a number below `bound`, which has to be positive.
+        fn below(&mut self, bound: usize) -> usize {
+            (self.next() % bound as u64) as usize
+        }
+    }
+
+    /// Generates `len` steps which never collapse the tunnel of `tunnel_len`.
+    ///
+    /// The first `tunnel_len` steps are random numbers from 1 to 100, and every later step is the sum
+    /// of 2 random elements of its preceding fragment, taken from different positions.
+    /// Steps grow exponentially, by a factor of up to about 5 every `tunnel_len` steps,
+    /// so `len` should stay below about 40 times `tunnel_len` for the steps to fit in `u128`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `tunnel_len` is smaller than 2 while `len` is greater than `tunnel_len`,
+    /// since no step can follow a fragment without a pair then, or if a step overflows `u128`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use turtles::gen::generate_safe_stream;
+    /// use turtles::is_stream_safe;
+    ///
+    /// for seed in 0..20 {
+    ///     let steps = generate_safe_stream(200, 5, seed);
+    ///
+    ///     assert_eq!(steps.len(), 200);
+    ///     assert!(is_stream_safe(steps.into_iter(), 5));
+    /// }
+    ///
+    /// assert_eq!(generate_safe_stream(50, 3, 7), generate_safe_stream(50, 3, 7));
+    /// ```
+    pub fn generate_safe_stream(len: usize, tunnel_len: usize, seed: u64) -> Vec<u128> {
+        let mut rng = SplitMix64(seed);
+        let mut steps = Vec::with_capacity(len);
+        extend_safe(&mut steps, len, tunnel_len, &mut rng);
+        steps
+    }
+
+    /// Generates `len` steps like [`generate_safe_stream`], except for the step at `collapse_index`,
+    /// which is the first step collapsing the tunnel of `tunnel_len`.
+    ///
+    /// The collapsing step is one more than twice the greatest element of its preceding fragment,
+    /// so it's greater than every sum of 2 of them. The steps after it are sums of 2 preceding elements again.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `tunnel_len` is 0, as there's no preceding element to build the collapsing step from,
+    /// if `collapse_index` isn't between `tunnel_len` and `len` (exclusive),
+    /// and in the same cases as [`generate_safe_stream`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use turtles::gen::generate_collapsing_stream;
+    /// use turtles::get_critical_number;
+    ///
+    /// for seed in 0..20 {
+    ///     let steps = generate_collapsing_stream(200, 5, seed, 120);
+    ///     let critical_number = get_critical_number(steps.iter().copied(), 5).unwrap();
+    ///
+    ///     assert_eq!(critical_number.index, 120);
+    ///     assert_eq!(critical_number.step, steps[120]);
+    /// }
+    /// ```
+    pub fn generate_collapsing_stream(
+        len: usize,
+        tunnel_len: usize,
+        seed: u64,
+        collapse_index: usize,
+    ) -> Vec<u128> {
+        assert!(
+            tunnel_len >= 1,
+            "a collapsing step needs a preceding fragment, but the tunnel length is 0"
+        );
+        assert!(
+            tunnel_len <= collapse_index && collapse_index < len,
+            "collapse index {collapse_index} has to be at least {tunnel_len} and below {len}"
+        );
+        let mut rng = SplitMix64(seed);
+        let mut steps = Vec::with_capacity(len);
+        extend_safe(&mut steps, collapse_index, tunnel_len, &mut rng);
+
+        // the fragment isn't empty, as checked above
+        let greatest = steps[collapse_index - tunnel_len..].iter().max().unwrap();
+        let collapsing_step = greatest
+            .checked_mul(2)
+            .and_then(|double| double.checked_add(1))
+            .expect("steps overflow u128");
+        steps.push(collapsing_step);
+
+        extend_safe(&mut steps, len, tunnel_len, &mut rng);
+        steps
+    }
+
+    /// Appends steps to `steps` until there are `len` of them, random ones while the preceding fragment
+    /// isn't full yet and sums of 2 of its elements afterwards.
+    fn extend_safe(steps: &mut Vec<u128>, len: usize, tunnel_len: usize, rng: &mut SplitMix64) {
+        while steps.len() < len.min(tunnel_len) {
+            steps.push(u128::from(rng.next() % MAX_INITIAL_STEP + 1));
+        }
+        if steps.len() < len {
+            assert!(
+                tunnel_len >= 2,
+                "a tunnel of length {tunnel_len} has no pair to sum up"
+            );
+        }
+        while steps.len() < len {
+            let fragment = &steps[steps.len() - tunnel_len..];
+            let a = rng.below(tunnel_len);
+            // a different position than `a`
+            let b = (a + 1 + rng.below(tunnel_len - 1)) % tunnel_len;
+            let step = fragment[a]
+                .checked_add(fragment[b])
+                .expect("steps overflow u128");
+            steps.push(step);
+        }
+    }
 }
@@ -1,26 +1,489 @@
+//! Prints the step at which the tunnel will crumble.
+//!
+//! Exits with code 0 if the tunnel is safe, 1 if it will crumble and 2 if the steps couldn't be read.
+//!
+//! Steps are parsed as `u64` by default, which is enough for most inputs and faster than `u128`.
+//! A step above `u64::MAX` is an error suggesting `--width u128`, as the input may not be readable twice.
+//!
+//! Many files can be passed at once, and each of them is checked separately with the same `--tunnel-len`,
+//! printing its results prefixed with its name. A file which cannot be read is reported, and the rest
+//! are still checked unless `--fail-fast` is passed, but the exit code is 2 in both cases.
+//! The tunnel length can also follow a single filename, as in `turtles steps.txt 25`,
+//! if the second argument is an integer.
+//!
+//! With `--all`, every step at which the tunnel collapses is listed with its line,
+//! followed by the number of them, instead of only the first one.
+//!
+//! With `--show-window`, the preceding steps which couldn't sum up to the critical step are printed
+//! in ascending order after it.
+//!
+//! With `--binary`, inputs are read as flat arrays of big-endian `u64` steps instead of text.
+//!
+//! With the `flate2` feature, inputs ending with `.gz`, or all of them with `--gzip`, are decompressed while they're read.
+//!
+//! The tunnel length passed in the arguments takes precedence over the `TURTLES_TUNNEL_LEN` environment variable,
+//! which in turn takes precedence over the default of 100.
+
 use std::env;
 use std::error;
-use std::fs;
+use std::fmt::{Debug, Display};
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, IsTerminal};
+use std::ops::{Add, Sub};
+use std::process::ExitCode;
+use std::str::FromStr;
+
+use turtles::{
+    find_collapse_weakness, get_all_critical_numbers, get_critical_number_reader_as,
+    get_critical_number_slice, get_critical_number_with_window, read_steps_as, read_steps_be_u64,
+    IndexedStep, TurtleError,
+};
+
+/// Tunnel length used when it's passed neither in the arguments nor in [`TUNNEL_LEN_VAR`]
+const DEFAULT_TUNNEL_LEN: usize = 100;
+
+/// Environment variable with the tunnel length used when it's not passed in the arguments
+const TUNNEL_LEN_VAR: &str = "TURTLES_TUNNEL_LEN";
+
+const USAGE: &str = "Usage: turtles [--json] [--weakness] [--format <template>] [--width u64|u128] [--tunnel-len <n>] [--fail-fast] [--gzip] [--binary] [--show-window] [--all] [<filename> | -]... [tunnel_len]";
+
+/// Options changing how the steps are read and how the answer is printed
+struct Options {
+    json: bool,
+    weakness: bool,
+    template: Option<String>,
+    gzip: bool,
+    binary: bool,
+    show_window: bool,
+    all: bool,
+}
+
+/// Integer type the steps are parsed as
+#[derive(Clone, Copy)]
+enum Width {
+    U64,
+    U128,
+}
+
+/// Opens the input from `filename`, or from stdin if it's `None` or `-`.
+/// It's decompressed if `gzip` is set or `filename` ends with `.gz`.
+fn open_input(
+    filename: Option<&str>,
+    gzip: bool,
+) -> Result<Box<dyn BufRead>, Box<dyn error::Error>> {
+    let input = open_plain_input(filename)?;
+    if gzip || filename.is_some_and(|filename| filename.ends_with(".gz")) {
+        decompress(input)
+    } else {
+        Ok(input)
+    }
+}
+
+fn open_plain_input(filename: Option<&str>) -> Result<Box<dyn BufRead>, Box<dyn error::Error>> {
+    match filename {
+        Some(filename) if filename != "-" => {
+            let file =
+                File::open(filename).map_err(|e| format!("Cannot read file '{filename}': {e}"))?;
+            Ok(Box::new(BufReader::new(file)))
+        }
+        _ => {
+            let stdin = io::stdin();
+            // nothing is piped, so we would wait for the user to type the steps
+            if filename.is_none() && stdin.is_terminal() {
+                return Err(format!(
+                    "No input available, pass a filename or pipe the steps to stdin. {USAGE}"
+                )
+                .into());
+            }
+            let mut stdin = stdin.lock();
+            if stdin.fill_buf()?.is_empty() {
+                return Err("No input available on stdin".into());
+            }
+            Ok(Box::new(stdin))
+        }
+    }
+}
+
+/// Decompresses gzip `input`, which may hold many concatenated members.
+#[cfg(feature = "flate2")]
+fn decompress(input: Box<dyn BufRead>) -> Result<Box<dyn BufRead>, Box<dyn error::Error>> {
+    let decoder = flate2::bufread::MultiGzDecoder::new(input);
+    Ok(Box::new(BufReader::new(decoder)))
+}
 
-use turtles::get_critical_number;
+#[cfg(not(feature = "flate2"))]
+fn decompress(_input: Box<dyn BufRead>) -> Result<Box<dyn BufRead>, Box<dyn error::Error>> {
+    Err("Reading compressed input needs turtles built with the 'flate2' feature".into())
+}
 
-fn main() -> Result<(), Box<dyn error::Error>> {
-    let args: Vec<String> = env::args().collect();
+/// Exit code when the arguments are invalid or the steps cannot be read
+const ERROR_EXIT_CODE: u8 = 2;
 
-    let filename = args.get(1).ok_or("Usage: turtles <filename>")?;
-    let input_text = fs::read_to_string(filename)?;
+fn main() -> ExitCode {
+    match run() {
+        Ok(false) => ExitCode::SUCCESS,
+        Ok(true) => ExitCode::FAILURE,
+        Err(e) => {
+            eprintln!("Error: {e}");
+            ExitCode::from(ERROR_EXIT_CODE)
+        }
+    }
+}
+
+/// Returns whether the tunnel will crumble.
+fn run() -> Result<bool, Box<dyn error::Error>> {
+    let mut json = false;
+    let mut weakness = false;
+    let mut template = None;
+    let mut width = Width::U64;
+    let mut tunnel_len = None;
+    let mut fail_fast = false;
+    let mut gzip = false;
+    let mut binary = false;
+    let mut show_window = false;
+    let mut all = false;
+    let mut args = Vec::new();
+    let mut all_args = env::args().skip(1);
+    while let Some(arg) = all_args.next() {
+        match arg.as_str() {
+            "--json" => json = true,
+            "--weakness" => weakness = true,
+            "--format" => {
+                let value = all_args
+                    .next()
+                    .ok_or_else(|| format!("Option '--format' needs a template. {USAGE}"))?;
+                // report a broken template before reading any input
+                render_template(&value, &IndexedStep { step: 0, index: 0 })?;
+                template = Some(value);
+            }
+            "--width" => {
+                width = match all_args.next().as_deref() {
+                    Some("u64") => Width::U64,
+                    Some("u128") => Width::U128,
+                    _ => return Err(format!("Option '--width' needs u64 or u128. {USAGE}").into()),
+                };
+            }
+            "--tunnel-len" => {
+                let value = all_args
+                    .next()
+                    .ok_or_else(|| format!("Option '--tunnel-len' needs a number. {USAGE}"))?;
+                tunnel_len = Some(parse_tunnel_len(&value)?);
+            }
+            "--fail-fast" => fail_fast = true,
+            "--gzip" => gzip = true,
+            "--binary" => binary = true,
+            "--show-window" => show_window = true,
+            "--all" => all = true,
+            flag if flag.starts_with("--") => {
+                return Err(format!("Unknown option '{flag}'. {USAGE}").into())
+            }
+            _ => args.push(arg),
+        }
+    }
+    if json && template.is_some() {
+        return Err("Options '--json' and '--format' cannot be used together".into());
+    }
+
+    // the older form passes the tunnel length after a single filename,
+    // so a second argument which isn't an integer is another file
+    if tunnel_len.is_none() && args.len() == 2 && args[1].parse::<i128>().is_ok() {
+        tunnel_len = Some(parse_tunnel_len(&args.pop().unwrap())?);
+    }
+    let tunnel_len = match tunnel_len {
+        Some(tunnel_len) => tunnel_len,
+        None => match env::var(TUNNEL_LEN_VAR) {
+            Ok(value) => {
+                parse_tunnel_len(&value).map_err(|e| format!("{e} in {TUNNEL_LEN_VAR}"))?
+            }
+            Err(env::VarError::NotPresent) => DEFAULT_TUNNEL_LEN,
+            Err(e) => return Err(format!("{TUNNEL_LEN_VAR}: {e}").into()),
+        },
+    };
+    // a sum of 2 needs at least 2 preceding steps
+    if tunnel_len < 2 {
+        return Err(format!("Tunnel length must be at least 2, got {tunnel_len}").into());
+    }
 
-    let steps_in_tunnel = input_text
-        .lines()
-        .filter_map(|line| line.parse::<u128>().ok());
+    let options = Options {
+        json,
+        weakness,
+        template,
+        gzip,
+        binary,
+        show_window,
+        all,
+    };
 
-    match get_critical_number(steps_in_tunnel, 100) {
-        Some(x) => println!(
+    // a single input keeps its results unlabeled
+    if args.len() <= 1 {
+        let filename = args.first().map(String::as_str);
+        return analyse_file(filename, None, tunnel_len, width, &options);
+    }
+
+    let mut crumbled = false;
+    let mut failed = 0;
+    for filename in &args {
+        match analyse_file(Some(filename), Some(filename), tunnel_len, width, &options) {
+            Ok(file_crumbled) => crumbled |= file_crumbled,
+            Err(e) if fail_fast => return Err(format!("{filename}: {e}").into()),
+            Err(e) => {
+                eprintln!("Error: {filename}: {e}");
+                failed += 1;
+            }
+        }
+    }
+    if failed > 0 {
+        return Err(format!("{failed} of {} files couldn't be checked", args.len()).into());
+    }
+
+    Ok(crumbled)
+}
+
+fn parse_tunnel_len(arg: &str) -> Result<usize, String> {
+    arg.parse()
+        .map_err(|_| format!("Invalid tunnel length '{arg}'"))
+}
+
+/// Checks the steps read from `filename` with [`open_input`],
+/// printing the results prefixed with the `label`, and returns whether the tunnel will crumble.
+fn analyse_file(
+    filename: Option<&str>,
+    label: Option<&str>,
+    tunnel_len: usize,
+    width: Width,
+    options: &Options,
+) -> Result<bool, Box<dyn error::Error>> {
+    let input = open_input(filename, options.gzip)?;
+
+    match width {
+        Width::U64 => analyse::<u64>(input, tunnel_len, label, options).map_err(|e| match e {
+            TurtleError::Parse { ref content, .. } if content.parse::<u128>().is_ok() => {
+                format!("{e}, pass '--width u128' for steps above {}", u64::MAX)
+            }
+            e => e.to_string(),
+        }),
+        Width::U128 => {
+            analyse::<u128>(input, tunnel_len, label, options).map_err(|e| e.to_string())
+        }
+    }
+    .map_err(Into::into)
+}
+
+/// Prints the answer for steps of type `T` and returns whether the tunnel will crumble.
+fn analyse<T>(
+    input: Box<dyn BufRead>,
+    tunnel_len: usize,
+    label: Option<&str>,
+    options: &Options,
+) -> Result<bool, TurtleError>
+where
+    T: Ord + Add<Output = T> + Sub<Output = T> + Clone + Debug + Display + FromStr + From<u64>,
+{
+    let Options {
+        json,
+        weakness,
+        ref template,
+        binary,
+        show_window,
+        all,
+        ..
+    } = *options;
+
+    // the contiguous range can be anywhere in the input, so all steps have to be read first
+    let (steps, critical_number, window) = if binary || weakness || show_window || all {
+        let steps: Vec<T> = if binary {
+            // the length is only checked after the whole input was read
+            read_steps_be_u64(input)?.into_iter().map(T::from).collect()
+        } else {
+            read_steps_as(input)?
+        };
+        let (critical_number, window) = if show_window {
+            get_critical_number_with_window(steps.iter().cloned(), tunnel_len).unzip()
+        } else {
+            (get_critical_number_slice(&steps, tunnel_len), None)
+        };
+        (steps, critical_number, window)
+    } else {
+        let critical_number = get_critical_number_reader_as(input, tunnel_len)?;
+        (Vec::new(), critical_number, None)
+    };
+
+    if all {
+        let collapses = get_all_critical_numbers(steps.iter().cloned(), tunnel_len);
+        print_all_collapses(&collapses, label, json, template.as_deref());
+    } else {
+        print_answer(critical_number.as_ref(), label, json, template.as_deref());
+    }
+
+    if let Some(window) = window {
+        let window: Vec<String> = window.iter().map(T::to_string).collect();
+        let answer = if json {
+            format!(r#"{{"window":[{}]}}"#, window.join(","))
+        } else {
+            format!("The preceding steps were [{}]", window.join(", "))
+        };
+        print_labeled(label, json, &answer);
+    }
+
+    if weakness {
+        print_weakness(
+            &steps,
+            critical_number.as_ref().map(|x| x.step.clone()),
+            label,
+            json,
+        );
+    }
+
+    Ok(critical_number.is_some())
+}
+
+/// Prints the first step at which the tunnel crumbles, if there is any.
+fn print_answer<T: Display>(
+    critical_number: Option<&IndexedStep<T>>,
+    label: Option<&str>,
+    json: bool,
+    template: Option<&str>,
+) {
+    let answer = match critical_number {
+        Some(x) => format_critical_number(x, json, template),
+        None if json => r#"{"result":null}"#.to_string(),
+        None => "The tunnel will not crumble".to_string(),
+    };
+    print_labeled(label, json, &answer);
+}
+
+/// Prints every step at which the tunnel crumbles, one per line in the order of the input, and their number.
+/// Without `json` or a `template`, the steps form a table of their lines and values.
+fn print_all_collapses<T: Display>(
+    collapses: &[IndexedStep<T>],
+    label: Option<&str>,
+    json: bool,
+    template: Option<&str>,
+) {
+    if !json && template.is_none() {
+        print_labeled(label, json, "line\tstep");
+    }
+    for x in collapses {
+        let row = if json || template.is_some() {
+            format_critical_number(x, json, template)
+        } else {
+            format!("{}\t{}", x.line_number(), x.step)
+        };
+        print_labeled(label, json, &row);
+    }
+
+    let count = collapses.len();
+    let total = if json {
+        format!(r#"{{"count":{count}}}"#)
+    } else {
+        format!("Total collapses: {count}")
+    };
+    print_labeled(label, json, &total);
+}
+
+/// Formats a step at which the tunnel crumbles as JSON, with the `template`, or as a sentence.
+fn format_critical_number<T: Display>(
+    x: &IndexedStep<T>,
+    json: bool,
+    template: Option<&str>,
+) -> String {
+    match template {
+        // same shape as `IndexedStep` serialized with the `serde` feature
+        _ if json => format!(r#"{{"step":{},"index":{}}}"#, x.step, x.index),
+        // the template was checked with the arguments
+        Some(template) => render_template(template, x).unwrap(),
+        None => format!(
             "The tunnel will crumble at number {} on line {}",
-            x.step, x.index + 1
+            x.step,
+            x.line_number()
         ),
-        None => println!("The tunnel will not crumble"),
     }
+}
+
+/// Prints the contiguous range of `steps` summing up to the `critical_step`,
+/// and the sum of its smallest and largest step.
+fn print_weakness<T>(steps: &[T], critical_step: Option<T>, label: Option<&str>, json: bool)
+where
+    T: Ord + Add<Output = T> + Sub<Output = T> + Clone + Display,
+{
+    let Some(critical_step) = critical_step else {
+        if !json {
+            print_labeled(label, json, "There is no weakness to look for");
+        }
+        return;
+    };
+
+    let answer = match find_collapse_weakness(steps, critical_step.clone()) {
+        Some((start, end)) => {
+            let range = &steps[start..=end];
+            // a range has at least 2 steps
+            let weakness =
+                range.iter().min().unwrap().clone() + range.iter().max().unwrap().clone();
+            if json {
+                format!(r#"{{"start":{start},"end":{end},"weakness":{weakness}}}"#)
+            } else {
+                format!(
+                    "The steps on lines {} to {} sum up to {critical_step}, and their weakness is {weakness}",
+                    start + 1,
+                    end + 1
+                )
+            }
+        }
+        None if json => r#"{"weakness":null}"#.to_string(),
+        None => format!("No contiguous steps sum up to {critical_step}"),
+    };
+    print_labeled(label, json, &answer);
+}
+
+/// Prints a line of the results, prefixed with the `label` of the file they come from.
+/// A JSON `line` gets the label as its first `file` field instead.
+fn print_labeled(label: Option<&str>, json: bool, line: &str) {
+    match label {
+        Some(label) if json => println!(r#"{{"file":{},{}"#, json_string(label), &line[1..]),
+        Some(label) => println!("{label}: {line}"),
+        None => println!("{line}"),
+    }
+}
+
+/// Quotes `text` as a JSON string.
+fn json_string(text: &str) -> String {
+    let mut quoted = String::from('"');
+    for c in text.chars() {
+        match c {
+            '"' => quoted.push_str(r#"\""#),
+            '\\' => quoted.push_str(r"\\"),
+            c if c.is_control() => quoted.push_str(&format!(r"\u{:04x}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
 
-    Ok(())
+/// Replaces `{step}`, `{index}` and `{line}` (the index starting from 1) in the `template`.
+fn render_template(
+    template: &str,
+    critical_number: &IndexedStep<impl Display>,
+) -> Result<String, String> {
+    let mut rendered = String::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        rendered.push_str(&rest[..start]);
+        let end = rest[start..]
+            .find('}')
+            .ok_or_else(|| format!("Unclosed placeholder in format '{template}'"))?;
+        match &rest[start + 1..start + end] {
+            "step" => rendered.push_str(&critical_number.step.to_string()),
+            "index" => rendered.push_str(&critical_number.index.to_string()),
+            "line" => rendered.push_str(&critical_number.line_number().to_string()),
+            placeholder => {
+                return Err(format!(
+                    "Unknown placeholder '{{{placeholder}}}' in format, expected {{step}}, {{index}} or {{line}}"
+                ))
+            }
+        }
+        rest = &rest[start + end + 1..];
+    }
+    rendered.push_str(rest);
+    Ok(rendered)
 }
@@ -0,0 +1,307 @@
+//! Runs the `turtles` binary on files written to a temporary directory.
+
+#![cfg(feature = "std")]
+
+use std::fs;
+use std::path::PathBuf;
+use std::process::{Command, Output};
+
+/// Writes `content` to a file named `name` in a directory of this test run and returns its path.
+fn write_steps(name: &str, content: impl AsRef<[u8]>) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("turtles-cli-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join(name);
+    fs::write(&path, content).unwrap();
+    path
+}
+
+fn turtles(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_turtles"))
+        .args(args)
+        .env_remove("TURTLES_TUNNEL_LEN")
+        .output()
+        .unwrap()
+}
+
+/// Works like [`turtles`], but with `TURTLES_TUNNEL_LEN` set to `tunnel_len`.
+fn turtles_with_tunnel_len_var(args: &[&str], tunnel_len: &str) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_turtles"))
+        .args(args)
+        .env("TURTLES_TUNNEL_LEN", tunnel_len)
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn checks_two_files_without_tunnel_len() {
+    let safe = write_steps("safe.txt", "1\n2\n3\n");
+    let crumbling = write_steps("crumbling.txt", "1\n2\n4\n");
+    let (safe, crumbling) = (safe.to_str().unwrap(), crumbling.to_str().unwrap());
+
+    // the default tunnel length of 100 is longer than both files
+    let output = turtles(&[safe, crumbling]);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+
+    assert_eq!(output.status.code(), Some(0), "{stdout}");
+    assert_eq!(
+        stdout,
+        format!("{safe}: The tunnel will not crumble\n{crumbling}: The tunnel will not crumble\n")
+    );
+
+    let output = turtles(&["--tunnel-len", "2", safe, crumbling]);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(
+        stdout,
+        format!(
+            "{safe}: The tunnel will not crumble\n{crumbling}: The tunnel will crumble at number 4 on line 3\n"
+        )
+    );
+}
+
+#[test]
+fn reads_tunnel_len_after_single_file() {
+    let steps = write_steps("legacy.txt", "1\n2\n4\n");
+
+    let output = turtles(&[steps.to_str().unwrap(), "2"]);
+
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "The tunnel will crumble at number 4 on line 3\n"
+    );
+}
+
+#[test]
+fn lists_all_collapses() {
+    let steps = write_steps("all.txt", "1\n2\n4\n6\n7\n20\n");
+    let steps = steps.to_str().unwrap();
+
+    let output = turtles(&["--all", "--tunnel-len", "2", steps]);
+
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "line\tstep\n3\t4\n5\t7\n6\t20\nTotal collapses: 3\n"
+    );
+
+    let output = turtles(&["--all", "--json", "--tunnel-len", "2", steps]);
+
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        r#"{"step":4,"index":2}
+{"step":7,"index":4}
+{"step":20,"index":5}
+{"count":3}
+"#
+    );
+}
+
+#[test]
+fn lists_no_collapses_of_safe_tunnel() {
+    let steps = write_steps("all-safe.txt", "1\n2\n3\n");
+    let steps = steps.to_str().unwrap();
+
+    let output = turtles(&["--all", "--tunnel-len", "2", steps]);
+
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "line\tstep\nTotal collapses: 0\n"
+    );
+
+    let output = turtles(&["--all", "--json", "--tunnel-len", "2", steps]);
+
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "{\"count\":0}\n");
+}
+
+#[test]
+fn prints_json_results() {
+    let safe = write_steps("json-safe.txt", "1\n2\n3\n");
+    let crumbling = write_steps("json-crumbling.txt", "1\n2\n4\n");
+    let (safe, crumbling) = (safe.to_str().unwrap(), crumbling.to_str().unwrap());
+
+    let output = turtles(&["--json", "--tunnel-len", "2", crumbling]);
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "{\"step\":4,\"index\":2}\n"
+    );
+
+    let output = turtles(&["--json", "--tunnel-len", "2", safe]);
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "{\"result\":null}\n"
+    );
+
+    // every result of many files starts with the file it comes from
+    let output = turtles(&["--json", "--tunnel-len", "2", safe, crumbling]);
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        format!(
+            "{{\"file\":\"{safe}\",\"result\":null}}\n{{\"file\":\"{crumbling}\",\"step\":4,\"index\":2}}\n"
+        )
+    );
+}
+
+#[test]
+fn exits_with_status_of_result() {
+    let safe = write_steps("status-safe.txt", "1\n2\n3\n");
+    let crumbling = write_steps("status-crumbling.txt", "1\n2\n4\n");
+    let invalid = write_steps("status-invalid.txt", "1\nx\n");
+
+    let output = turtles(&["--tunnel-len", "2", safe.to_str().unwrap()]);
+    assert_eq!(output.status.code(), Some(0));
+
+    let output = turtles(&["--tunnel-len", "2", crumbling.to_str().unwrap()]);
+    assert_eq!(output.status.code(), Some(1));
+
+    let output = turtles(&["--tunnel-len", "2", invalid.to_str().unwrap()]);
+    assert_eq!(output.status.code(), Some(2));
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        "Error: line 2: invalid number 'x'\n"
+    );
+
+    let missing = safe.with_file_name("status-missing.txt");
+    let output = turtles(&["--tunnel-len", "2", missing.to_str().unwrap()]);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(output.stdout.is_empty());
+}
+
+#[test]
+fn renders_format_template() {
+    let steps = write_steps("format.txt", "1\n2\n4\n");
+    let steps = steps.to_str().unwrap();
+
+    let output = turtles(&[
+        "--format",
+        "{step}@{line} ({index})",
+        "--tunnel-len",
+        "2",
+        steps,
+    ]);
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "4@3 (2)\n");
+
+    // a broken template is reported before any input is read
+    let output = turtles(&["--format", "{nope}", steps]);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(output.stdout.is_empty());
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        "Error: Unknown placeholder '{nope}' in format, expected {step}, {index} or {line}\n"
+    );
+
+    let output = turtles(&["--format", "{step", steps]);
+    assert_eq!(output.status.code(), Some(2));
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        "Error: Unclosed placeholder in format '{step'\n"
+    );
+}
+
+#[test]
+fn suggests_wider_steps() {
+    let steps = write_steps("width.txt", "1\n2\n18446744073709551616\n");
+    let steps = steps.to_str().unwrap();
+
+    let output = turtles(&["--tunnel-len", "2", steps]);
+    assert_eq!(output.status.code(), Some(2));
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        "Error: line 3: invalid number '18446744073709551616', \
+         pass '--width u128' for steps above 18446744073709551615\n"
+    );
+
+    let output = turtles(&["--width", "u128", "--tunnel-len", "2", steps]);
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "The tunnel will crumble at number 18446744073709551616 on line 3\n"
+    );
+}
+
+#[test]
+fn reads_tunnel_len_from_environment() {
+    let steps = write_steps("env.txt", "1\n2\n4\n6\n7\n");
+    let steps = steps.to_str().unwrap();
+
+    let output = turtles_with_tunnel_len_var(&[steps], "2");
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "The tunnel will crumble at number 4 on line 3\n"
+    );
+
+    // the argument takes precedence over the environment
+    let output = turtles_with_tunnel_len_var(&["--tunnel-len", "3", steps], "2");
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "The tunnel will crumble at number 7 on line 5\n"
+    );
+
+    let output = turtles_with_tunnel_len_var(&[steps], "abc");
+    assert_eq!(output.status.code(), Some(2));
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        "Error: Invalid tunnel length 'abc' in TURTLES_TUNNEL_LEN\n"
+    );
+
+    // an invalid variable doesn't matter when the argument is passed
+    let output = turtles_with_tunnel_len_var(&["--tunnel-len", "2", steps], "abc");
+    assert_eq!(output.status.code(), Some(1));
+}
+
+#[test]
+fn reads_binary_steps() {
+    let bytes: Vec<u8> = [1u64, 2, 4]
+        .iter()
+        .flat_map(|step| step.to_be_bytes())
+        .collect();
+    let whole = write_steps("steps.bin", &bytes);
+    let partial = write_steps("partial.bin", &bytes[..20]);
+
+    let output = turtles(&["--binary", "--tunnel-len", "2", whole.to_str().unwrap()]);
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "The tunnel will crumble at number 4 on line 3\n"
+    );
+
+    let output = turtles(&["--binary", "--tunnel-len", "2", partial.to_str().unwrap()]);
+    assert_eq!(output.status.code(), Some(2));
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        "Error: binary input of 20 bytes isn't made of whole 8-byte steps\n"
+    );
+}
+
+#[test]
+fn shows_window_before_collapse() {
+    let crumbling = write_steps("window.txt", "5\n4\n7\n9\n14\n");
+    let safe = write_steps("window-safe.txt", "1\n2\n3\n");
+    let (crumbling, safe) = (crumbling.to_str().unwrap(), safe.to_str().unwrap());
+
+    let output = turtles(&["--show-window", "--tunnel-len", "3", crumbling]);
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "The tunnel will crumble at number 14 on line 5\nThe preceding steps were [4, 7, 9]\n"
+    );
+
+    let output = turtles(&["--show-window", "--json", "--tunnel-len", "3", crumbling]);
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "{\"step\":14,\"index\":4}\n{\"window\":[4,7,9]}\n"
+    );
+
+    // there's no window to show without a collapse
+    let output = turtles(&["--show-window", "--tunnel-len", "3", safe]);
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "The tunnel will not crumble\n"
+    );
+}
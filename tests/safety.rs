@@ -0,0 +1,76 @@
+//! Compares the checks of `SortedTunnel` with a brute-force search over every pair of elements.
+
+use proptest::prelude::*;
+use turtles::tunnel_utils::{Backend, SortedTunnel};
+
+/// Checks every pair of positions, so an element is added to itself only if `allow_self_pair` is set
+/// or it appears at another position as well.
+fn naive_is_safe(tunnel: &[i64], new_step: i64, allow_self_pair: bool) -> bool {
+    (0..tunnel.len()).any(|i| {
+        let first = if allow_self_pair { i } else { i + 1 };
+        (first..tunnel.len()).any(|j| tunnel[i] + tunnel[j] == new_step)
+    })
+}
+
+fn backends() -> impl Strategy<Value = Backend> {
+    prop_oneof![Just(Backend::BTreeMap), Just(Backend::SortedVec)]
+}
+
+proptest! {
+    // small values make repeated elements and reachable targets likely
+    #[test]
+    fn is_tunnel_safe_matches_naive(
+        tunnel in prop::collection::vec(-20i64..20, 0..12),
+        new_step in -45i64..45,
+        allow_self_pair in any::<bool>(),
+        backend in backends(),
+    ) {
+        let sorted_tunnel = SortedTunnel::with_backend(tunnel.clone(), backend)
+            .allow_self_pair(allow_self_pair);
+
+        prop_assert_eq!(
+            sorted_tunnel.is_tunnel_safe(new_step),
+            naive_is_safe(&tunnel, new_step, allow_self_pair)
+        );
+    }
+
+    #[test]
+    fn safe_pair_sums_up_to_target(
+        tunnel in prop::collection::vec(-20i64..20, 0..12),
+        new_step in -45i64..45,
+        allow_self_pair in any::<bool>(),
+    ) {
+        let sorted_tunnel = SortedTunnel::new(tunnel.clone()).allow_self_pair(allow_self_pair);
+
+        match sorted_tunnel.safe_pair(new_step) {
+            Some((a, b)) => {
+                prop_assert_eq!(a + b, new_step);
+                prop_assert!(tunnel.contains(&a) && tunnel.contains(&b));
+                let count = tunnel.iter().filter(|&&step| step == a).count();
+                prop_assert!(a != b || allow_self_pair || count > 1);
+            }
+            None => prop_assert!(!naive_is_safe(&tunnel, new_step, allow_self_pair)),
+        }
+    }
+
+    #[test]
+    fn shifted_tunnel_matches_naive(
+        tunnel in prop::collection::vec(-20i64..20, 1..10),
+        new_steps in prop::collection::vec(-20i64..20, 0..10),
+        new_step in -45i64..45,
+        backend in backends(),
+    ) {
+        let mut sorted_tunnel = SortedTunnel::with_backend(tunnel.clone(), backend);
+        let mut window = tunnel;
+        for step in new_steps {
+            sorted_tunnel.shift_right(step);
+            window.remove(0);
+            window.push(step);
+        }
+
+        prop_assert_eq!(
+            sorted_tunnel.is_tunnel_safe(new_step),
+            naive_is_safe(&window, new_step, false)
+        );
+    }
+}